@@ -1,24 +1,35 @@
-use std::{f32::consts::PI, time::Duration};
+use std::f32::consts::PI;
 
 use crate::{
+    colony::{Colony, ColonyId},
+    control::{sim_timer, SimControlPlugin, SimReset, SimSet, SimUpdate},
+    food::FoodSource,
+    grids::WorldGrid,
+    obstacle::{ObstaclePlugin, Obstacles},
     pheromone::Pheromones,
-    utils::{calc_rotatio_angle, get_rand_unit_vec2, get_steering_force},
+    utils::{calc_rotatio_angle, get_rand_unit_vec2, get_steering_force, SimRng},
     *,
 };
 use bevy::{
     math::{vec2, vec3},
     prelude::{
-        AssetServer, Color, Commands, Component, Handle, Image, IntoSystemConfigs, Plugin, Quat,
-        Query, Res, ResMut, Resource, Startup, Transform, Update, Vec2, Vec3, With,
+        Added, AssetServer, Changed, Color, Commands, Component, Entity, Event, EventReader,
+        EventWriter, Handle, Image, IntoSystemConfigs, Plugin, Quat, Query, Res, ResMut, Resource,
+        Startup, Transform, Update, Vec2, Vec3, With,
     },
     sprite::{Sprite, SpriteBundle},
-    time::common_conditions::on_timer,
-    window::Window,
 };
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 pub struct AntPlugin;
 
-#[derive(Debug)]
+/// 蚂蚁的贴图，无窗口模式下不添加
+pub struct AntRenderPlugin;
+
+/// 没有目标时随机游走的力度，默认 300° 对应 0.2
+const WANDER_FORCE_PER_DEG: f32 = 0.2 / 300.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AntTask {
     FindFood,
     FindHome,
@@ -29,250 +40,554 @@ pub struct CurrentTask(pub AntTask);
 #[derive(Component)]
 pub struct Ant;
 #[derive(Component)]
-pub struct Velocity(Vec2);
+pub struct Velocity(pub Vec2);
 #[derive(Component)]
-pub struct Acceleration(Vec2);
+pub struct Acceleration(pub Vec2);
+/// 蚂蚁搜索信息素的半径（信息素格子数），随时间逐渐变大
 #[derive(Resource)]
-struct AntScanRadius(f32);
+pub struct AntScanRadius(pub f32);
+/// 蚂蚁留下的信息素强度，离开巢穴或食物越久越弱
 #[derive(Component)]
-struct PhStrength(f32);
+pub struct PhStrength(pub f32);
+/// 体力，移动时消耗，耗尽就饿死
+#[derive(Component, Debug)]
+pub struct Energy(pub f32);
+/// 年龄和这只蚂蚁的寿命（秒）
+#[derive(Component, Debug)]
+pub struct Age {
+    pub age: f32,
+    pub lifespan: f32,
+}
+/// 这一趟出门时的年龄，送达食物时据此算出往返用时
+#[derive(Component, Debug)]
+pub struct TripStart(pub f32);
+/// 蚂蚁在食物源拾取了一份食物
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FoodPickedUp {
+    pub colony: ColonyId,
+}
+/// 蚂蚁把食物送回了蚁巢，trip_time 是从出门到送达的秒数
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FoodDelivered {
+    pub colony: ColonyId,
+    pub trip_time: f32,
+}
+/// 累计死亡的蚂蚁数，重置时清零
+#[derive(Resource, Debug, Default)]
+pub struct AntDeaths {
+    pub starved: u32,
+    pub old_age: u32,
+}
 
 impl Plugin for AntPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+        if !app.is_plugin_added::<ObstaclePlugin>() {
+            app.add_plugins(ObstaclePlugin);
+        }
+        let config = app.world.resource::<SimConfig>().clone();
+
+        if !app.world.contains_resource::<SimRng>() {
+            app.insert_resource(SimRng::new(config.seed));
+        }
+
+        // 固定执行顺序，保证同样的种子跑出同样的结果
         app.add_systems(Startup, setup)
-            .insert_resource(AntScanRadius(INITIAL_ANT_PH_SCAN_RADIUS))
-            .add_systems(
-                Update,
-                check_wall_collision.run_if(on_timer(Duration::from_secs_f32(0.1))),
-            )
-            .add_systems(Update, update_position.after(check_wall_collision))
-            .add_systems(
-                Update,
-                drop_pheromone.run_if(on_timer(Duration::from_secs_f32(ANT_PH_DROP_INTERVAL))),
-            )
-            .add_systems(
-                Update,
-                check_home_food_collisions.run_if(on_timer(Duration::from_secs_f32(0.1))),
-            )
-            .add_systems(
-                Update,
-                decay_ph_stength.run_if(on_timer(Duration::from_secs_f32(
-                    ANT_PH_STRENGTH_DECAY_INTERVAL,
-                ))),
-            )
+            .add_event::<ResetSimulation>()
+            .add_event::<FoodPickedUp>()
+            .add_event::<FoodDelivered>()
+            .insert_resource(AntScanRadius(config.initial_ant_ph_scan_radius))
+            .init_resource::<AntDeaths>()
+            .add_systems(Update, reset_ants.in_set(SimReset))
             .add_systems(
-                Update,
-                periodic_direction_update.run_if(on_timer(Duration::from_secs_f32(
-                    ANT_DIRECTION_UPDATE_INTERVAL,
-                ))),
-            )
-            .add_systems(
-                Update,
-                update_scan_radius.run_if(on_timer(Duration::from_secs_f32(1.0))),
+                SimUpdate,
+                (
+                    periodic_direction_update
+                        .run_if(sim_timer(|c| c.ant_direction_update_interval)),
+                    update_position,
+                    update_energy_and_age,
+                    check_home_food_collisions.run_if(sim_timer(|_| 0.1)),
+                    drop_pheromone.run_if(sim_timer(|c| c.ant_ph_drop_interval)),
+                    decay_ph_stength.run_if(sim_timer(|c| c.ant_ph_strength_decay_interval)),
+                    update_scan_radius.run_if(sim_timer(|_| 1.0)),
+                )
+                    .chain()
+                    .in_set(SimSet::Ants),
             );
     }
 }
 
-fn setup(mut commands: Commands, assert_server: Res<AssetServer>) {
-    for _ in 0..NUM_ANTS {
-        commands.spawn((
-            SpriteBundle {
-                texture: assert_server.load(SPRITE_ANT),
-                sprite: Sprite {
-                    color: Color::rgb(1.1, 1.1, 1.0),
-                    ..Default::default()
-                },
-                transform: Transform::from_xyz(HOME_LOCATION.0, HOME_LOCATION.1, ANT_Z_INDEX)
-                    .with_scale(Vec3::splat(ANT_SPRITE_SCALE)),
+impl Plugin for AntRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, (attach_ant_sprites, update_ant_sprites));
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<SimConfig>, mut rng: ResMut<SimRng>) {
+    spawn_ants(&mut commands, &config, &mut rng);
+}
+
+fn spawn_ants(commands: &mut Commands, config: &SimConfig, rng: &mut SimRng) {
+    for (id, colony) in config.colonies.iter().enumerate() {
+        let (home_x, home_y) = colony.location;
+        for _ in 0..config.num_ants {
+            spawn_ant(commands, config, rng, ColonyId(id), vec2(home_x, home_y));
+        }
+    }
+}
+
+/// 在 pos 处生成一只属于 colony 的出门找食物的蚂蚁
+pub fn spawn_ant(
+    commands: &mut Commands,
+    config: &SimConfig,
+    rng: &mut SimRng,
+    colony: ColonyId,
+    pos: Vec2,
+) {
+    let randomness = config.ant_lifespan_randomness;
+    let lifespan = config.ant_lifespan * (1.0 + rng.rng.gen_range(-randomness..=randomness));
+    commands.spawn((
+        Transform::from_xyz(pos.x, pos.y, config.ant_z_index)
+            .with_scale(Vec3::splat(config.ant_sprite_scale)),
+        Ant,
+        colony,
+        CurrentTask(AntTask::FindFood),
+        Velocity(get_rand_unit_vec2(&mut rng.rng)),
+        Acceleration(Vec2::ZERO),
+        PhStrength(config.ant_initial_ph_strength),
+        Energy(config.ant_max_energy),
+        Age { age: 0.0, lifespan },
+        TripStart(0.0),
+    ));
+}
+
+///重置时移除所有蚂蚁，按当前配置重新生成
+fn reset_ants(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetSimulation>,
+    ant_query: Query<Entity, With<Ant>>,
+    mut scan_radius: ResMut<AntScanRadius>,
+    mut deaths: ResMut<AntDeaths>,
+    mut rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    for entity in ant_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    scan_radius.0 = config.initial_ant_ph_scan_radius;
+    *deaths = AntDeaths::default();
+    // 指定了种子时重置后从头复现，否则换一个新种子
+    *rng = SimRng::new(config.seed);
+    spawn_ants(&mut commands, &config, &mut rng);
+}
+
+///蚂蚁的颜色：所属蚁群配置的颜色，配置里找不到时用白色
+fn ant_color(config: &SimConfig, colony: ColonyId, task: &AntTask) -> Color {
+    let Some(colony) = config.colonies.get(colony.0) else {
+        return Color::WHITE;
+    };
+    let (r, g, b) = match task {
+        AntTask::FindFood => colony.ant_color,
+        AntTask::FindHome => colony.ant_color_with_food,
+    };
+    Color::rgb(r, g, b)
+}
+
+///蚂蚁的贴图：找食物时空手，回家时带着食物
+fn ant_sprite(task: &AntTask) -> &'static str {
+    match task {
+        AntTask::FindFood => SPRITE_ANT,
+        AntTask::FindHome => SPRITE_ANT_WITH_FOOD,
+    }
+}
+
+///新生成的蚂蚁加上贴图，从快照或录像恢复的蚂蚁可能已经带着食物
+fn attach_ant_sprites(
+    mut commands: Commands,
+    ant_query: Query<(Entity, &Transform, &ColonyId, &CurrentTask), Added<Ant>>,
+    assert_server: Res<AssetServer>,
+    config: Res<SimConfig>,
+) {
+    for (entity, transform, colony, ant_task) in ant_query.iter() {
+        commands.entity(entity).insert(SpriteBundle {
+            texture: assert_server.load(ant_sprite(&ant_task.0)),
+            sprite: Sprite {
+                color: ant_color(&config, *colony, &ant_task.0),
                 ..Default::default()
             },
-            Ant,
-            CurrentTask(AntTask::FindFood),
-            Velocity(get_rand_unit_vec2()),
-            Acceleration(Vec2::ZERO),
-            PhStrength(ANT_INITIAL_PH_STRENGTH),
-        ));
+            transform: *transform,
+            ..Default::default()
+        });
     }
 }
 
-fn check_wall_collision(
-    mut ant_query: Query<(&Transform, &Velocity, &mut Acceleration), With<Ant>>,
-    window: Query<&Window>,
+///任务变化时切换贴图：找食物/带着食物回家
+#[allow(clippy::type_complexity)]
+fn update_ant_sprites(
+    mut ant_query: Query<
+        (&CurrentTask, &ColonyId, &mut Sprite, &mut Handle<Image>),
+        (With<Ant>, Changed<CurrentTask>),
+    >,
+    assert_server: Res<AssetServer>,
+    config: Res<SimConfig>,
 ) {
-    let window = window.single();
-    let (w, h) = (window.width(), window.height());
-
-    for (transform, velocity, mut acceleration) in ant_query.iter_mut() {
-        let border = 20.0;
-        let top_left = (-w / 2.0, h / 2.0);
-        let bottom_right = (w / 2.0, -h / 2.0);
-        let x_bound = transform.translation.x < top_left.0 + border
-            || transform.translation.x >= bottom_right.0 - border;
-        let y_bound = transform.translation.y >= top_left.1 - border
-            || transform.translation.y < bottom_right.1 + border;
-        if x_bound || y_bound {
-            let mut rng = thread_rng();
-            let target = vec2(rng.gen_range(-200.0..200.0), rng.gen_range(-200.0..200.0));
-            acceleration.0 +=
-                get_steering_force(target, transform.translation.truncate(), velocity.0);
-        }
+    for (ant_task, colony, mut sprite, mut image_handle) in ant_query.iter_mut() {
+        *image_handle = assert_server.load(ant_sprite(&ant_task.0));
+        sprite.color = ant_color(&config, *colony, &ant_task.0);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn periodic_direction_update(
-    mut ant_query: Query<(&mut Acceleration, &Transform, &CurrentTask, &Velocity), With<Ant>>,
+    mut ant_query: Query<
+        (
+            &mut Acceleration,
+            &Transform,
+            &CurrentTask,
+            &Velocity,
+            &ColonyId,
+        ),
+        With<Ant>,
+    >,
     mut pheromones: ResMut<Pheromones>,
     scan_radius: Res<AntScanRadius>,
+    food_query: Query<&FoodSource>,
+    colony_query: Query<&Colony>,
+    obstacles: Res<Obstacles>,
+    mut rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
 ) {
+    let rng = &mut rng.rng;
     pheromones.clear_steer_cache();
+    let pull_radius = config.ant_target_auto_pull_radius;
+    let wander_force = config.ant_direction_randomness_deg * WANDER_FORCE_PER_DEG;
 
-    for (mut acceleration, transform, current_task, velocity) in ant_query.iter_mut() {
+    for (mut acceleration, transform, current_task, velocity, colony_id) in ant_query.iter_mut() {
         let cur_pos = transform.translation;
 
         let target = match current_task.0 {
-            AntTask::FindFood => {
-                let dist_to_food = transform.translation.distance_squared(vec3(
-                    FOOD_LOCATION.0,
-                    FOOD_LOCATION.1,
-                    0.0,
-                ));
-                if dist_to_food <= ANT_TARGET_AUTO_PULL_RADIUS * ANT_TARGET_AUTO_PULL_RADIUS {
-                    Some(vec2(FOOD_LOCATION.0, FOOD_LOCATION.1))
-                } else {
-                    None
-                }
-            }
-            AntTask::FindHome => {
-                let dist_to_home = transform.translation.distance_squared(vec3(
-                    HOME_LOCATION.0,
-                    HOME_LOCATION.1,
-                    0.0,
-                ));
-                if dist_to_home <= ANT_TARGET_AUTO_PULL_RADIUS * ANT_TARGET_AUTO_PULL_RADIUS {
-                    Some(vec2(HOME_LOCATION.0, HOME_LOCATION.1))
-                } else {
-                    None
-                }
-            }
+            AntTask::FindFood => food_query
+                .iter()
+                .filter(|food| !food.is_depleted())
+                .map(|food| (food.pos, food.pos.distance_squared(cur_pos.truncate())))
+                .filter(|(_, dist)| *dist <= pull_radius * pull_radius)
+                .filter(|(pos, _)| obstacles.is_visible(cur_pos.truncate(), *pos))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(pos, _)| pos),
+            AntTask::FindHome => colony_query
+                .iter()
+                .filter(|colony| colony.id == *colony_id)
+                .map(|colony| (colony.pos, colony.pos.distance_squared(cur_pos.truncate())))
+                .filter(|(_, dist)| *dist <= pull_radius * pull_radius)
+                .filter(|(pos, _)| obstacles.is_visible(cur_pos.truncate(), *pos))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(pos, _)| pos),
         };
 
         let target = match target {
-            None => match current_task.0 {
-                AntTask::FindFood => pheromones.to_food.get_steer_target(&cur_pos, scan_radius.0),
-                AntTask::FindHome => pheromones.to_home.get_steer_target(&cur_pos, scan_radius.0),
-            },
+            None => {
+                // 重新加载配置后蚁群可能变少，旧蚁群的蚂蚁在被重置删掉之前没有图层
+                let Some(layers) = pheromones.colonies.get_mut(colony_id.0) else {
+                    continue;
+                };
+                let grid = match current_task.0 {
+                    AntTask::FindFood => &mut layers.to_food,
+                    AntTask::FindHome => &mut layers.to_home,
+                };
+                match config.ant_steering {
+                    SteeringModel::Midpoint => {
+                        grid.get_steer_target(&cur_pos, scan_radius.0, &obstacles)
+                    }
+                    SteeringModel::Antenna => antenna_target(
+                        grid,
+                        cur_pos.truncate(),
+                        velocity.0,
+                        &obstacles,
+                        &config,
+                        rng,
+                    ),
+                }
+            }
             a @ Some(_) => a,
         };
 
         match target {
             None => {
-                acceleration.0 += get_rand_unit_vec2() * 0.2;
+                acceleration.0 += get_rand_unit_vec2(rng) * wander_force;
             }
             Some(target) => {
                 let steering_force =
                     get_steering_force(target, transform.translation.truncate(), velocity.0);
-                let mut rng = rand::thread_rng();
-                acceleration.0 += steering_force * rng.gen_range(0.4..=ANT_STEERING_FORCE_FACTOR);
+                acceleration.0 += steering_force
+                    * rng.gen_range(MIN_STEERING_FORCE_FACTOR..=config.ant_steering_force_factor);
             }
         }
     }
 }
 
+/// 触角模型：在前方和左右两侧各取样一处信息素，中间最强就往前走，两侧都比中间强就随机选一侧，
+/// 否则转向较强的一侧；墙里或隔着墙的触角读不到信号，三处都没有信号时返回 None
+fn antenna_target(
+    grid: &WorldGrid,
+    pos: Vec2,
+    velocity: Vec2,
+    obstacles: &Obstacles,
+    config: &SimConfig,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    let heading = velocity.try_normalize()?;
+    let angle = config.ant_antenna_angle_deg.to_radians();
+    let sense = |rotation: f32| {
+        let sensor = pos + Vec2::from_angle(rotation).rotate(heading) * config.ant_antenna_distance;
+        let value = if obstacles.is_visible(pos, sensor) {
+            grid.sample(sensor)
+        } else {
+            0.0
+        };
+        (sensor, value)
+    };
+    let (left, center, right) = (sense(angle), sense(0.0), sense(-angle));
+    if left.1 <= 0.0 && center.1 <= 0.0 && right.1 <= 0.0 {
+        return None;
+    }
+
+    let (target, _) = if center.1 >= left.1 && center.1 >= right.1 {
+        center
+    } else if center.1 < left.1 && center.1 < right.1 {
+        if rng.gen_bool(0.5) {
+            left
+        } else {
+            right
+        }
+    } else if left.1 > right.1 {
+        left
+    } else {
+        right
+    };
+    Some(target)
+}
+
+#[allow(clippy::type_complexity)]
 fn check_home_food_collisions(
     mut ant_query: Query<
         (
             &Transform,
-            &mut Sprite,
             &mut Velocity,
             &mut CurrentTask,
             &mut PhStrength,
-            &mut Handle<Image>,
+            &mut Energy,
+            &ColonyId,
+            &Age,
+            &mut TripStart,
         ),
         With<Ant>,
     >,
-    assert_server: Res<AssetServer>,
+    mut food_query: Query<&mut FoodSource>,
+    mut colony_query: Query<&mut Colony>,
+    mut pickups: EventWriter<FoodPickedUp>,
+    mut deliveries: EventWriter<FoodDelivered>,
+    config: Res<SimConfig>,
 ) {
-    for (transform, mut sprite, mut velocity, mut ant_task, mut ph_strength, mut image_handle) in
-        ant_query.iter_mut()
+    for (
+        transform,
+        mut velocity,
+        mut ant_task,
+        mut ph_strength,
+        mut energy,
+        colony_id,
+        age,
+        mut trip_start,
+    ) in ant_query.iter_mut()
     {
-        let dist_to_home =
-            transform
-                .translation
-                .distance_squared(vec3(HOME_LOCATION.0, HOME_LOCATION.1, 0.0));
-        if dist_to_home < HOME_RADIUS * HOME_RADIUS {
+        let pos = transform.translation.truncate();
+        //只认自己蚁群的巢穴
+        let colony = colony_query.iter_mut().find(|colony| {
+            colony.id == *colony_id
+                && colony.pos.distance_squared(pos) < colony.radius * colony.radius
+        });
+        if let Some(mut colony) = colony {
             match ant_task.0 {
                 AntTask::FindFood => {}
                 AntTask::FindHome => {
+                    //把食物交给蚁巢
+                    colony.deliver();
                     velocity.0 *= -1.0;
+                    deliveries.send(FoodDelivered {
+                        colony: *colony_id,
+                        trip_time: age.age - trip_start.0,
+                    });
                 }
             };
+            trip_start.0 = age.age;
             ant_task.0 = AntTask::FindFood;
-            ph_strength.0 = ANT_INITIAL_PH_STRENGTH;
-            *image_handle = assert_server.load(SPRITE_ANT);
-            sprite.color = Color::rgb(1.0, 1.0, 2.5);
+            ph_strength.0 = config.ant_initial_ph_strength;
+
+            //从蚁巢库存里吃饱，库存不够就能吃多少吃多少
+            let hunger = config.ant_max_energy - energy.0;
+            let eaten = f32::min(hunger / config.ant_food_energy, colony.stockpile);
+            colony.stockpile -= eaten;
+            energy.0 += eaten * config.ant_food_energy;
         }
 
-        let dist_to_food =
-            transform
-                .translation
-                .distance_squared(vec3(FOOD_LOCATION.0, FOOD_LOCATION.1, 0.0));
-        if dist_to_food <= FOOD_PICKUP_RADIUS * FOOD_PICKUP_RADIUS {
+        let food = food_query
+            .iter_mut()
+            .find(|food| food.pos.distance_squared(pos) <= food.radius * food.radius);
+        if let Some(mut food) = food {
             match ant_task.0 {
                 AntTask::FindFood => {
+                    //搬完的食物不能再拾取
+                    if !food.take() {
+                        continue;
+                    }
                     velocity.0 *= -1.0;
+                    pickups.send(FoodPickedUp { colony: *colony_id });
                 }
                 AntTask::FindHome => {}
             };
 
             ant_task.0 = AntTask::FindHome;
-            ph_strength.0 = ANT_INITIAL_PH_STRENGTH;
-            *image_handle = assert_server.load(SPRITE_ANT_WITH_FOOD);
-            sprite.color = Color::rgb(1.0, 2.0, 1.0);
+            ph_strength.0 = config.ant_initial_ph_strength;
         }
     }
 }
 
 fn drop_pheromone(
-    ant_query: Query<(&Transform, &CurrentTask, &PhStrength), With<Ant>>,
+    ant_query: Query<(&Transform, &CurrentTask, &PhStrength, &ColonyId), With<Ant>>,
     mut pheronones: ResMut<Pheromones>,
+    obstacles: Res<Obstacles>,
 ) {
-    //1.蚂蚁经过，留下信号
-    for (transform, ant_task, ph_strength) in ant_query.iter() {
-        let x = transform.translation.x as i32;
-        let y = transform.translation.y as i32;
+    //1.蚂蚁经过，在自己蚁群的图层留下信号，墙里不留
+    for (transform, ant_task, ph_strength, colony_id) in ant_query.iter() {
+        if obstacles.is_blocked(transform.translation.truncate()) {
+            continue;
+        }
+        let x = transform.translation.x.floor() as i32;
+        let y = transform.translation.y.floor() as i32;
+        let Some(layers) = pheronones.colonies.get_mut(colony_id.0) else {
+            continue;
+        };
 
         match ant_task.0 {
-            AntTask::FindFood => pheronones.to_home.emit_signal(&(x, y), ph_strength.0),
-            AntTask::FindHome => pheronones.to_food.emit_signal(&(x, y), ph_strength.0),
+            AntTask::FindFood => layers.to_home.emit_signal(&(x, y), ph_strength.0),
+            AntTask::FindHome => layers.to_food.emit_signal(&(x, y), ph_strength.0),
         }
     }
 }
 
-fn decay_ph_stength(mut ant_query: Query<&mut PhStrength, With<Ant>>) {
+fn decay_ph_stength(mut ant_query: Query<&mut PhStrength, With<Ant>>, config: Res<SimConfig>) {
     for mut ph_strength in ant_query.iter_mut() {
-        ph_strength.0 = f32::max(ph_strength.0 - ANT_PH_STRENGTH_DECAY_RATE, 0.0);
+        ph_strength.0 = f32::max(ph_strength.0 - config.ant_ph_strength_decay_rate, 0.0);
     }
 }
 
-fn update_scan_radius(mut scan_radius: ResMut<AntScanRadius>) {
-    if scan_radius.0 > INITIAL_ANT_PH_SCAN_RADIUS * ANT_PH_SCAN_RADIUS_SCALE {
+fn update_scan_radius(mut scan_radius: ResMut<AntScanRadius>, config: Res<SimConfig>) {
+    if scan_radius.0 > config.initial_ant_ph_scan_radius * config.ant_ph_scan_radius_scale {
         return;
     }
 
-    scan_radius.0 += ANT_PH_SCAN_RADIUS_INCREMENT;
+    scan_radius.0 += config.ant_ph_scan_radius_increment;
+}
+
+///消耗体力、增加年龄，饿死或老死的蚂蚁移除
+fn update_energy_and_age(
+    mut commands: Commands,
+    mut ant_query: Query<(Entity, &mut Energy, &mut Age, &mut CurrentTask), With<Ant>>,
+    mut deaths: ResMut<AntDeaths>,
+    config: Res<SimConfig>,
+) {
+    let dt = 1.0 / config.tick_hz;
+    let drain = if config.ant_speed > 0.0 {
+        config.ant_energy_drain_rate * dt
+    } else {
+        0.0
+    };
+    let hungry = config.ant_max_energy * config.ant_hunger_threshold;
+
+    for (entity, mut energy, mut age, mut ant_task) in ant_query.iter_mut() {
+        age.age += dt;
+        energy.0 -= drain;
+
+        //饿了就吃掉搬着的食物，重新出去找
+        if energy.0 < hungry && matches!(ant_task.0, AntTask::FindHome) {
+            energy.0 = f32::min(energy.0 + config.ant_food_energy, config.ant_max_energy);
+            ant_task.0 = AntTask::FindFood;
+        }
+
+        if energy.0 <= 0.0 {
+            deaths.starved += 1;
+            commands.entity(entity).despawn();
+        } else if age.age >= age.lifespan {
+            deaths.old_age += 1;
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+///撞墙时沿墙滑动：只保留不会撞墙的那个方向，两个方向都走不通就掉头；
+///已经在墙里（编辑器刚画的墙）时直接走出来
+fn slide_along_walls(
+    old_pos: Vec3,
+    new_pos: Vec3,
+    velocity: &mut Velocity,
+    obstacles: &Obstacles,
+) -> Vec3 {
+    let (old, new) = (old_pos.truncate(), new_pos.truncate());
+    if !obstacles.is_blocked(new) || (obstacles.is_blocked(old) && obstacles.in_world(new)) {
+        return new_pos;
+    }
+
+    let slide_x = vec3(new_pos.x, old_pos.y, old_pos.z);
+    let slide_y = vec3(old_pos.x, new_pos.y, old_pos.z);
+    let mut slides = [
+        (
+            slide_x,
+            vec2(velocity.0.x.signum(), 0.0),
+            velocity.0.x.abs(),
+        ),
+        (
+            slide_y,
+            vec2(0.0, velocity.0.y.signum()),
+            velocity.0.y.abs(),
+        ),
+    ];
+    //先试速度分量大的方向
+    if slides[1].2 > slides[0].2 {
+        slides.swap(0, 1);
+    }
+    for (pos, direction, component) in slides {
+        if component > 0.0 && !obstacles.is_blocked(pos.truncate()) {
+            velocity.0 = direction;
+            return pos;
+        }
+    }
+
+    velocity.0 *= -1.0;
+    old_pos
 }
 
 fn update_position(
     mut ant_query: Query<(&mut Transform, &mut Velocity, &mut Acceleration), With<Ant>>,
+    obstacles: Res<Obstacles>,
+    config: Res<SimConfig>,
 ) {
     for (mut transform, mut velocity, mut acceleration) in ant_query.iter_mut() {
         let old_pos = transform.translation;
+        // ant_speed 是每秒的距离，每步走 1 / tick_hz 秒
+        let speed = config.ant_speed / config.tick_hz * obstacles.speed_factor(old_pos.truncate());
         if !acceleration.0.is_nan() {
             velocity.0 = (velocity.0 + acceleration.0).normalize();
             let new_translation =
-                transform.translation + vec3(velocity.0.x, velocity.0.y, 0.0) * ANT_SPEED;
+                transform.translation + vec3(velocity.0.x, velocity.0.y, 0.0) * speed;
             if !new_translation.is_nan() {
-                transform.translation = new_translation;
+                transform.translation =
+                    slide_along_walls(old_pos, new_translation, &mut velocity, &obstacles);
             }
         }
 
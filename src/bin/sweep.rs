@@ -0,0 +1,203 @@
+//! 参数扫描：`cargo run --release --bin sweep -- sweeps/decay_vs_ants.ron [--jobs N] [--output runs.csv]`
+//! 每组参数和种子无窗口跑一遍，多组并行，最后按参数组合汇总
+
+use std::{fs, time::Instant};
+
+use ants::sweep::{default_jobs, run_parallel, RunSummary, SweepRun, SweepSpec};
+
+#[derive(Default)]
+struct Args {
+    spec_path: Option<String>,
+    jobs: Option<usize>,
+    output: Option<String>,
+}
+
+fn main() {
+    let args = parse_args();
+    let Some(spec_path) = args.spec_path else {
+        eprintln!("usage: sweep <sweep.ron> [--jobs N] [--output runs.csv]");
+        std::process::exit(1);
+    };
+    let spec = SweepSpec::load(&spec_path).unwrap_or_else(|e| exit_with(e));
+    let runs = spec.runs().unwrap_or_else(|e| exit_with(e));
+    let jobs = args
+        .jobs
+        .unwrap_or_else(default_jobs)
+        .clamp(1, runs.len().max(1));
+
+    eprintln!(
+        "{} runs of {} ticks on {jobs} threads",
+        runs.len(),
+        spec.ticks
+    );
+    let start = Instant::now();
+    let results = run_all(&spec, &runs, jobs);
+    eprintln!("finished in {:.2?}", start.elapsed());
+
+    if let Some(path) = &args.output {
+        if let Err(e) = fs::write(path, runs_csv(&runs, &results)) {
+            eprintln!("failed to write {path}: {e}");
+            std::process::exit(1);
+        }
+    }
+    print_summary(&spec, &runs, &results);
+}
+
+fn exit_with(e: impl std::fmt::Display) -> ! {
+    eprintln!("{e}");
+    std::process::exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut parsed = Args::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--jobs" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(jobs) => parsed.jobs = Some(jobs),
+                None => exit_with("--jobs expects a positive integer"),
+            },
+            "--output" => parsed.output = args.next(),
+            _ if parsed.spec_path.is_none() && !arg.starts_with("--") => {
+                parsed.spec_path = Some(arg)
+            }
+            _ => exit_with(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    parsed
+}
+
+/// 跑完一组打印一行进度
+fn run_all(spec: &SweepSpec, runs: &[SweepRun], jobs: usize) -> Vec<RunSummary> {
+    let mut done = 0;
+    run_parallel(
+        runs,
+        spec.ticks,
+        spec.metrics_interval,
+        jobs,
+        |index, summary| {
+            done += 1;
+            eprintln!(
+                "[{done}/{}] {} seed {}: {:.1} deliveries/min",
+                runs.len(),
+                format_params(&runs[index].params),
+                runs[index].seed,
+                summary.throughput,
+            );
+        },
+    )
+}
+
+fn format_params(params: &[(String, f64)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|v| format!("{v:.3}")).unwrap_or_default()
+}
+
+/// 每次运行一行
+fn runs_csv(runs: &[SweepRun], results: &[RunSummary]) -> String {
+    let mut lines = Vec::new();
+    let mut header: Vec<String> = runs
+        .first()
+        .map(|run| run.params.iter().map(|(name, _)| name.clone()).collect())
+        .unwrap_or_default();
+    header.extend(
+        [
+            "seed",
+            "first_delivery",
+            "throughput",
+            "round_trip",
+            "trail_length",
+            "delivered",
+            "ants",
+        ]
+        .map(String::from),
+    );
+    lines.push(header.join(","));
+
+    for (run, summary) in runs.iter().zip(results) {
+        let mut row: Vec<String> = run.params.iter().map(|(_, v)| v.to_string()).collect();
+        row.extend([
+            run.seed.to_string(),
+            optional(summary.first_delivery),
+            format!("{:.3}", summary.throughput),
+            optional(summary.round_trip),
+            optional(summary.trail_length),
+            summary.delivered.to_string(),
+            summary.ants.to_string(),
+        ]);
+        lines.push(row.join(","));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn mean_std(values: &[f64]) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    Some((mean, var.sqrt()))
+}
+
+/// 同一组参数的所有种子取平均，吞吐量附带标准差
+fn print_summary(spec: &SweepSpec, runs: &[SweepRun], results: &[RunSummary]) {
+    let per_combo = spec.seeds.max(1) as usize;
+    let header = [
+        "params",
+        "first delivery (s)",
+        "throughput (/min)",
+        "trail length",
+        "delivered",
+        "ants",
+    ];
+    let mut rows = vec![header.map(String::from).to_vec()];
+
+    for (runs, results) in runs.chunks(per_combo).zip(results.chunks(per_combo)) {
+        let collect = |f: fn(&RunSummary) -> Option<f64>| -> Vec<f64> {
+            results.iter().filter_map(f).collect()
+        };
+        let mean = |values: Vec<f64>| {
+            mean_std(&values)
+                .map(|(mean, _)| format!("{mean:.1}"))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let throughput = mean_std(&collect(|s| Some(s.throughput)))
+            .map(|(mean, std)| format!("{mean:.1} ± {std:.1}"))
+            .unwrap_or_default();
+
+        rows.push(vec![
+            format_params(&runs[0].params),
+            mean(collect(|s| s.first_delivery)),
+            throughput,
+            mean(collect(|s| s.trail_length)),
+            mean(collect(|s| Some(s.delivered as f64))),
+            mean(collect(|s| Some(s.ants as f64))),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
@@ -0,0 +1,124 @@
+//! 遗传算法调参：`cargo run --release --bin tune -- sweeps/tune_behaviour.ron [--jobs N] [--output configs/tuned.ron]`
+//! 每代结束都把目前最好的参数写成配置文件，中途停下也能直接用 `--config` 加载
+
+use std::time::Instant;
+
+use ants::{
+    sweep::default_jobs,
+    tune::{save_config, TuneParam, TuneSpec, Tuner},
+};
+
+const DEFAULT_OUTPUT: &str = "configs/tuned.ron";
+
+#[derive(Default)]
+struct Args {
+    spec_path: Option<String>,
+    jobs: Option<usize>,
+    output: Option<String>,
+}
+
+fn main() {
+    let args = parse_args();
+    let Some(spec_path) = args.spec_path else {
+        eprintln!("usage: tune <tune.ron> [--jobs N] [--output {DEFAULT_OUTPUT}]");
+        std::process::exit(1);
+    };
+    let output = args.output.unwrap_or_else(|| DEFAULT_OUTPUT.to_string());
+    let spec = TuneSpec::load(&spec_path).unwrap_or_else(|e| exit_with(e));
+    let mut tuner = Tuner::new(spec).unwrap_or_else(|e| exit_with(e));
+    let jobs = args.jobs.unwrap_or_else(default_jobs).max(1);
+
+    eprintln!(
+        "{} generations of {} ({} seeds x {} ticks each) on {jobs} threads",
+        tuner.spec.generations, tuner.spec.population, tuner.spec.seeds, tuner.spec.ticks
+    );
+    let start = Instant::now();
+    let mut best_fitness = f64::MIN;
+    for generation in 0..tuner.spec.generations.max(1) {
+        if generation > 0 {
+            tuner.next_generation();
+        }
+        let stats = tuner
+            .evaluate(jobs, |index, seed, summary| {
+                eprintln!(
+                    "  individual {index} seed {seed}: delivered {} ({:.1}/min)",
+                    summary.delivered, summary.throughput
+                );
+            })
+            .unwrap_or_else(|e| exit_with(e));
+
+        let best = tuner.best().expect("population was just evaluated").clone();
+        eprintln!(
+            "generation {}: best {:.4} mean {:.4} [{}] {:.2?}",
+            stats.generation,
+            stats.best,
+            stats.mean,
+            format_genes(&tuner, &best.genes),
+            start.elapsed()
+        );
+
+        let fitness = best.fitness.unwrap_or(f64::MIN);
+        if fitness > best_fitness {
+            best_fitness = fitness;
+            let config = tuner.config(&best.genes).unwrap_or_else(|e| exit_with(e));
+            let comment = format!(
+                "由 `tune {spec_path}` 生成，第 {} 代\n{:?} = {fitness:.4}\n{}",
+                stats.generation,
+                tuner.spec.fitness,
+                format_genes(&tuner, &best.genes)
+            );
+            save_config(&config, &output, &comment).unwrap_or_else(|e| exit_with(e));
+        }
+    }
+
+    let best = tuner.best().expect("population was evaluated");
+    println!("best fitness: {best_fitness:.4} ({:?})", tuner.spec.fitness);
+    for (param, value) in tuner.spec.params.iter().zip(best.genes.iter()) {
+        println!("  {}: {}", param.name, format_value(param, *value));
+    }
+    println!("config written to {output}");
+}
+
+fn exit_with(e: impl std::fmt::Display) -> ! {
+    eprintln!("{e}");
+    std::process::exit(1);
+}
+
+fn format_value(param: &TuneParam, value: f64) -> String {
+    if param.integer {
+        format!("{value}")
+    } else {
+        format!("{value:.4}")
+    }
+}
+
+fn format_genes(tuner: &Tuner, genes: &[f64]) -> String {
+    tuner
+        .spec
+        .params
+        .iter()
+        .zip(genes)
+        .map(|(param, value)| format!("{}={}", param.name, format_value(param, *value)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_args() -> Args {
+    let mut parsed = Args::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--jobs" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(jobs) => parsed.jobs = Some(jobs),
+                None => exit_with("--jobs expects a positive integer"),
+            },
+            "--output" => parsed.output = args.next(),
+            _ if parsed.spec_path.is_none() && !arg.starts_with("--") => {
+                parsed.spec_path = Some(arg)
+            }
+            _ => exit_with(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    parsed
+}
@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::{Input, KeyCode, Plugin, Res, ResMut, Resource, Time, Update},
+};
+use bevy_egui::{
+    egui::{
+        self,
+        plot::{Legend, Line, Plot, PlotPoints},
+        Slider,
+    },
+    EguiContexts, EguiPlugin,
+};
+
+use crate::{
+    control::SimTiming,
+    metrics::{MetricsHistory, MetricsPlugin, MetricsSample},
+};
+
+/// 性能曲线最多保留的秒数（实际时间）
+const PERF_HISTORY_SECS: f64 = 120.0;
+
+/// 每张图的高度
+const PLOT_HEIGHT: f32 = 120.0;
+
+/// 图表窗口，按 C 开关：每分钟送达数、找食物/回家的蚂蚁数、信息素总量、帧率和每步耗时；
+/// 数据来自 [`MetricsHistory`]，需要 [`MetricsPlugin`]
+pub struct ChartsPlugin;
+
+#[derive(Resource, Debug, Clone)]
+pub struct Charts {
+    pub open: bool,
+    /// 只显示最近这么多秒（模拟时间）的统计
+    pub window_secs: f64,
+    /// (实际时间, 帧率, 每步耗时毫秒)
+    perf: VecDeque<(f64, f64, Option<f64>)>,
+}
+
+impl Default for Charts {
+    fn default() -> Self {
+        Self {
+            open: false,
+            window_secs: 300.0,
+            perf: VecDeque::new(),
+        }
+    }
+}
+
+impl Plugin for ChartsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        if !app.is_plugin_added::<MetricsPlugin>() {
+            app.add_plugins(MetricsPlugin::default());
+        }
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+
+        app.init_resource::<Charts>()
+            .add_systems(Update, (record_performance, charts_window));
+    }
+}
+
+fn record_performance(
+    mut charts: ResMut<Charts>,
+    time: Res<Time>,
+    diagnostics: Res<DiagnosticsStore>,
+    timing: Res<SimTiming>,
+) {
+    let Some(fps) = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+    else {
+        return;
+    };
+
+    let now = time.elapsed_seconds_f64();
+    charts.perf.push_back((now, fps, timing.tick_ms()));
+    while charts
+        .perf
+        .front()
+        .is_some_and(|(t, _, _)| now - t > PERF_HISTORY_SECS)
+    {
+        charts.perf.pop_front();
+    }
+}
+
+/// 把统计里的一个值画成一条线，横轴是模拟时间
+fn metric_line(
+    samples: &[&MetricsSample],
+    name: &str,
+    value: impl Fn(&MetricsSample) -> Option<f64>,
+) -> Line {
+    let points: PlotPoints = samples
+        .iter()
+        .filter_map(|sample| Some([sample.time, value(sample)?]))
+        .collect();
+    Line::new(points).name(name)
+}
+
+/// 一种图层所有蚁群的信息素总量
+fn pheromone_mass(sample: &MetricsSample, layer: &str) -> f64 {
+    sample
+        .grids
+        .iter()
+        .filter(|grid| grid.layer == layer)
+        .map(|grid| grid.total_strength as f64)
+        .sum()
+}
+
+fn charts_window(
+    mut contexts: EguiContexts,
+    mut charts: ResMut<Charts>,
+    history: Res<MetricsHistory>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let ctx = contexts.ctx_mut();
+    if !ctx.wants_keyboard_input() && keys.just_pressed(KeyCode::C) {
+        charts.open = !charts.open;
+    }
+    if !charts.open {
+        return;
+    }
+
+    let latest = history.samples.back().map_or(0.0, |sample| sample.time);
+    let samples: Vec<&MetricsSample> = history
+        .samples
+        .iter()
+        .filter(|sample| latest - sample.time <= charts.window_secs)
+        .collect();
+
+    let mut open = charts.open;
+    egui::Window::new("Charts")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.add(
+                Slider::new(&mut charts.window_secs, 30.0..=3600.0)
+                    .logarithmic(true)
+                    .text("window (sim s)"),
+            );
+
+            ui.label("deliveries per minute");
+            Plot::new("deliveries")
+                .height(PLOT_HEIGHT)
+                .include_y(0.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .show(ui, |plot| {
+                    plot.line(metric_line(&samples, "deliveries/min", |s| {
+                        Some(s.deliveries as f64 / s.interval as f64 * 60.0)
+                    }));
+                });
+
+            ui.label("ants by task");
+            Plot::new("tasks")
+                .height(PLOT_HEIGHT)
+                .include_y(0.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    plot.line(metric_line(&samples, "searching", |s| {
+                        Some(s.ants_find_food as f64)
+                    }));
+                    plot.line(metric_line(&samples, "returning", |s| {
+                        Some(s.ants_find_home as f64)
+                    }));
+                });
+
+            ui.label("pheromone mass");
+            Plot::new("pheromones")
+                .height(PLOT_HEIGHT)
+                .include_y(0.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    plot.line(metric_line(&samples, "to_food", |s| {
+                        Some(pheromone_mass(s, "to_food"))
+                    }));
+                    plot.line(metric_line(&samples, "to_home", |s| {
+                        Some(pheromone_mass(s, "to_home"))
+                    }));
+                });
+
+            ui.label(format!("performance (last {PERF_HISTORY_SECS}s)"));
+            Plot::new("performance")
+                .height(PLOT_HEIGHT)
+                .include_y(0.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    let fps: PlotPoints = charts.perf.iter().map(|&(t, fps, _)| [t, fps]).collect();
+                    let tick_ms: PlotPoints = charts
+                        .perf
+                        .iter()
+                        .filter_map(|&(t, _, ms)| Some([t, ms?]))
+                        .collect();
+                    plot.line(Line::new(fps).name("fps"));
+                    plot.line(Line::new(tick_ms).name("tick time (ms)"));
+                });
+        });
+    charts.open = open;
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use bevy::{
+    math::vec2,
+    prelude::{
+        Added, AssetServer, Color, Commands, Component, Entity, EventReader, IntoSystemConfigs,
+        Plugin, Query, Res, ResMut, Startup, Transform, Update, Vec2, Vec3, With,
+    },
+    sprite::{Sprite, SpriteBundle},
+};
+
+use crate::{
+    ant::{spawn_ant, Ant},
+    control::{sim_timer, SimControlPlugin, SimReset, SimSet, SimUpdate},
+    utils::SimRng,
+    ResetSimulation, SimConfig, SPRITE_ANT_COLONY,
+};
+
+/// 统计送达速率的时间窗口（秒）
+const DELIVERY_RATE_WINDOW: f32 = 10.0;
+
+/// 蚂蚁所属的蚁群，是 [`SimConfig::colonies`] 和信息素图层的下标
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColonyId(pub usize);
+
+/// 蚁巢：收集蚂蚁送回的食物，攒够了就孵化新蚂蚁
+#[derive(Component, Debug, Clone)]
+pub struct Colony {
+    pub id: ColonyId,
+    pub pos: Vec2,
+    pub radius: f32,
+    /// 库存食物
+    pub stockpile: f32,
+    /// 累计送达的食物
+    pub delivered: u32,
+    /// 累计孵化的蚂蚁
+    pub births: u32,
+    /// 最近一个统计窗口内每秒送达的食物
+    pub delivery_rate: f32,
+    pub(crate) delivered_in_window: u32,
+}
+
+impl Colony {
+    pub fn new(id: ColonyId, pos: Vec2, radius: f32) -> Self {
+        Self {
+            id,
+            pos,
+            radius,
+            stockpile: 0.0,
+            delivered: 0,
+            births: 0,
+            delivery_rate: 0.0,
+            delivered_in_window: 0,
+        }
+    }
+
+    pub fn deliver(&mut self) {
+        self.stockpile += 1.0;
+        self.delivered += 1;
+        self.delivered_in_window += 1;
+    }
+}
+
+pub struct ColonyPlugin;
+
+/// 蚁巢贴图，无窗口模式下不添加
+pub struct ColonyRenderPlugin;
+
+impl Plugin for ColonyPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+
+        app.add_systems(Startup, setup)
+            .add_event::<ResetSimulation>()
+            .add_systems(Update, reset_colony.in_set(SimReset))
+            .add_systems(
+                SimUpdate,
+                (
+                    spawn_colony_ants.run_if(sim_timer(|c| c.ant_spawn_interval)),
+                    update_delivery_rate.run_if(sim_timer(|_| DELIVERY_RATE_WINDOW)),
+                )
+                    .after(SimSet::Ants)
+                    .before(SimSet::Pheromones),
+            );
+    }
+}
+
+impl Plugin for ColonyRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, attach_colony_sprites);
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
+    spawn_colonies(&mut commands, &config);
+}
+
+fn spawn_colonies(commands: &mut Commands, config: &SimConfig) {
+    for (id, colony) in config.colonies.iter().enumerate() {
+        let (home_x, home_y) = colony.location;
+        commands.spawn(Colony::new(
+            ColonyId(id),
+            vec2(home_x, home_y),
+            config.home_radius,
+        ));
+    }
+}
+
+fn reset_colony(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetSimulation>,
+    colony_query: Query<Entity, With<Colony>>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    for entity in colony_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_colonies(&mut commands, &config);
+}
+
+///库存够了就孵化一只蚂蚁，每个蚁群不超过 max_ants
+fn spawn_colony_ants(
+    mut commands: Commands,
+    mut colony_query: Query<&mut Colony>,
+    ant_query: Query<&ColonyId, With<Ant>>,
+    mut rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
+) {
+    let mut num_ants = HashMap::new();
+    for id in ant_query.iter() {
+        *num_ants.entry(*id).or_insert(0) += 1;
+    }
+
+    // 按编号顺序孵化，保证同样的种子跑出同样的结果
+    let mut colonies = colony_query.iter_mut().collect::<Vec<_>>();
+    colonies.sort_by_key(|colony| colony.id.0);
+    for mut colony in colonies {
+        let num_ants = num_ants.get(&colony.id).copied().unwrap_or(0);
+        if num_ants >= config.max_ants || colony.stockpile < config.ant_spawn_cost {
+            continue;
+        }
+
+        colony.stockpile -= config.ant_spawn_cost;
+        colony.births += 1;
+        let pos = colony.pos;
+        spawn_ant(&mut commands, &config, &mut rng, colony.id, pos);
+    }
+}
+
+fn update_delivery_rate(mut colony_query: Query<&mut Colony>) {
+    for mut colony in colony_query.iter_mut() {
+        colony.delivery_rate = colony.delivered_in_window as f32 / DELIVERY_RATE_WINDOW;
+        colony.delivered_in_window = 0;
+    }
+}
+
+fn attach_colony_sprites(
+    mut commands: Commands,
+    colony_query: Query<(Entity, &Colony), Added<Colony>>,
+    assert_server: Res<AssetServer>,
+    config: Res<SimConfig>,
+) {
+    for (entity, colony) in colony_query.iter() {
+        commands.entity(entity).insert(SpriteBundle {
+            texture: assert_server.load(SPRITE_ANT_COLONY),
+            sprite: Sprite {
+                color: Color::rgb(1.5, 1.5, 1.5),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(colony.pos.x, colony.pos.y, 2.0)
+                .with_scale(Vec3::splat(config.home_sprite_scale)),
+            ..Default::default()
+        });
+    }
+}
@@ -1,19 +1,9 @@
-// Global
-pub const W: f32 = 1920.0;
-pub const H: f32 = 1080.0;
+use std::{fmt, fs, io, path::Path};
 
-pub const BG_COLOR: (u8, u8, u8) = (0, 0, 0);
+use bevy::prelude::{Event, Resource};
+use serde::{Deserialize, Serialize};
 
-// Ant Colony
-pub const HOME_LOCATION: (f32, f32) = (759.0, -350.0);
-pub const HOME_SPRITE_SCALE: f32 = 2.5;
-pub const HOME_RADIUS: f32 = 30.0;
-
-// Food
-pub const FOOD_LOCATION: (f32, f32) = (-750.0, 400.0);
-//pub const FOOD_LOCATION: (f32, f32) = (-379.5, 00.0);
-pub const FOOD_PICKUP_RADIUS: f32 = 30.0;
-pub const FOOD_SPRITE_SCALE: f32 = 2.0;
+use crate::decay::DecayModel;
 
 // Sprites
 pub const SPRITE_ANT: &str = "ant.png";
@@ -21,40 +11,673 @@ pub const SPRITE_ANT_WITH_FOOD: &str = "ant_with_food.png";
 pub const SPRITE_ANT_COLONY: &str = "nest.png";
 pub const SPRITE_FOOD: &str = "food.png";
 
-// Ants
-pub const NUM_ANTS: u32 = 5000;
-pub const ANT_SPEED: f32 = 1.5;
-pub const ANT_DIRECTION_RANDOMNESS_DEG: f32 = 300.0;
-pub const ANT_DIRECTION_UPDATE_INTERVAL: f32 = 0.5;
-pub const ANT_SPRITE_SCALE: f32 = 0.3;
-pub const ANT_Z_INDEX: f32 = 3.0;
-pub const ANT_INITIAL_PH_STRENGTH: f32 = 32.0;
-pub const ANT_PH_STRENGTH_DECAY_RATE: f32 = 1.2;
-pub const ANT_PH_STRENGTH_DECAY_INTERVAL: f32 = 0.5;
-pub const ANT_PH_DROP_INTERVAL: f32 = 0.7;
-pub const INITIAL_ANT_PH_SCAN_RADIUS: f32 = 15.0;
-pub const ANT_PH_SCAN_RADIUS_INCREMENT: f32 = 0.1;
-pub const ANT_PH_SCAN_RADIUS_SCALE: f32 = 1.8;
-pub const ANT_STEERING_FORCE_FACTOR: f32 = 0.7;
-pub const ANT_TARGET_AUTO_PULL_RADIUS: f32 = 100.0;
-
-// Pheromones
-pub const MAX_PHEROMONE_STRENGTH: f32 = 500.0;
-pub const PH_DECAY_RATE: f32 = 0.08;
-pub const PH_DECAY_INTERVAL: f32 = 0.1;
-pub const PH_IMG_UPDATE_SEC: f32 = 0.1;
-pub const PH_GRID_VIZ_MIN_STRENGTH: u8 = 1;
-pub const PH_UNIT_GRID_SIZE: usize = 5;
-pub const PH_KD_TREE_UPDATE_INTERVAL: f32 = 2.0;
-pub const PH_GRID_OPACITY: u8 = 255;
-
-pub const PH_COLOR_TO_FOOD: (u8, u8, u8) = (2, 79, 2);
-pub const PH_COLOR_TO_HOME: (u8, u8, u8) = (200, 81, 112);
-pub const PH_CACHE_GRID_SIZE: i32 = 10;
-
-// Path Viz
-pub const VIZ_COLOR_TO_HOME: (u8, u8, u8) = (17, 106, 123);
-pub const VIZ_COLOR_TO_FOOD: (u8, u8, u8) = (92, 46, 126);
-pub const VIZ_DECAY_RATE: f32 = 0.3;
-pub const VIZ_COLOR_STRENGTH: f32 = 8.0;
-pub const VIZ_MAX_COLOR_STRENGTH: f32 = 50.0;
+/// 模拟参数，启动时可以从 RON 文件加载，缺省字段使用默认值
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    // Global
+    pub w: f32,
+    pub h: f32,
+    /// 世界没有边界，蚂蚁可以走出 w x h 的范围（用 PanCam 拖动查看）；信息素网格不能用 Dense，
+    /// 用 Chunked 时信息素只画有信号的块，障碍物和路径显示仍只画 w x h 的范围
+    pub world_unbounded: bool,
+    pub bg_color: (u8, u8, u8),
+    /// 随机数种子，不设置时每次运行随机选取
+    pub seed: Option<u64>,
+    /// PNG 地图路径，设置后巢穴、食物、障碍物和地形都从图里生成，见 `maps/README.md`
+    pub map: Option<String>,
+    /// 地图里一个纯绿像素对应的食物量
+    pub map_food_per_pixel: f32,
+    /// 模拟每秒运行的步数，和帧率无关
+    pub tick_hz: f32,
+    /// 一帧最多补跑的步数，机器跟不上时会变慢而不是卡死
+    pub max_ticks_per_frame: u32,
+
+    // Ant Colony
+    /// 互相竞争的蚁群，各自有巢穴、蚂蚁颜色和私有的信息素图层，共享食物
+    pub colonies: Vec<ColonySpec>,
+    pub home_sprite_scale: f32,
+    pub home_radius: f32,
+    /// 孵化一只蚂蚁消耗的食物
+    pub ant_spawn_cost: f32,
+    /// 两次孵化之间的间隔（秒）
+    pub ant_spawn_interval: f32,
+    /// 每个蚁群的蚂蚁数量上限，孵化到这个数量后停止
+    pub max_ants: u32,
+
+    // Food
+    pub food_sources: Vec<FoodSpec>,
+    pub food_sprite_scale: f32,
+
+    // Obstacles
+    /// 墙和障碍物，按信息素网格对齐成阻挡格子
+    pub obstacles: Vec<ShapeSpec>,
+    pub obstacle_color: (u8, u8, u8),
+    /// 难走的地形，蚂蚁在里面的速度乘以 `speed`
+    pub terrain: Vec<TerrainSpec>,
+    pub terrain_color: (u8, u8, u8),
+
+    // Ants
+    /// 每个蚁群初始的蚂蚁数量
+    pub num_ants: u32,
+    /// 移动速度（世界坐标/秒），和 `tick_hz` 无关
+    pub ant_speed: f32,
+    pub ant_direction_randomness_deg: f32,
+    pub ant_direction_update_interval: f32,
+    pub ant_sprite_scale: f32,
+    pub ant_z_index: f32,
+    pub ant_initial_ph_strength: f32,
+    pub ant_ph_strength_decay_rate: f32,
+    pub ant_ph_strength_decay_interval: f32,
+    pub ant_ph_drop_interval: f32,
+    pub initial_ant_ph_scan_radius: f32,
+    pub ant_ph_scan_radius_increment: f32,
+    pub ant_ph_scan_radius_scale: f32,
+    pub ant_steering_force_factor: f32,
+    /// 蚂蚁根据信息素转向的方式
+    pub ant_steering: SteeringModel,
+    /// 触角模型：左右触角和前进方向的夹角
+    pub ant_antenna_angle_deg: f32,
+    /// 触角模型：触角离蚂蚁的距离（世界坐标）
+    pub ant_antenna_distance: f32,
+    pub ant_target_auto_pull_radius: f32,
+    /// 体力上限，新生的蚂蚁体力是满的
+    pub ant_max_energy: f32,
+    /// 移动时每秒消耗的体力，体力耗尽就会饿死
+    pub ant_energy_drain_rate: f32,
+    /// 吃掉一份食物恢复的体力，在蚁巢里从库存中吃
+    pub ant_food_energy: f32,
+    /// 体力低于上限的这个比例时，回家的蚂蚁会吃掉自己搬的食物
+    pub ant_hunger_threshold: f32,
+    /// 寿命（秒），到了就老死
+    pub ant_lifespan: f32,
+    /// 每只蚂蚁的寿命在 `ant_lifespan` 上下随机浮动的比例
+    pub ant_lifespan_randomness: f32,
+
+    // Pheromones
+    pub max_pheromone_strength: f32,
+    /// 信息素的衰减方式
+    pub ph_decay_model: DecaySpec,
+    pub ph_decay_rate: f32,
+    pub ph_decay_interval: f32,
+    /// 每次衰减后扩散到周围 8 个格子的信号比例（3x3 核），0 不扩散
+    pub ph_diffusion_rate: f32,
+    /// 扩散出去的信号损失的比例，0 总量守恒，1 扩散出去的全部消失
+    pub ph_diffusion_loss: f32,
+    pub ph_img_update_sec: f32,
+    pub ph_grid_viz_min_strength: u8,
+    pub ph_unit_grid_size: usize,
+    /// 信息素网格的存储方式
+    pub ph_grid_backend: GridBackend,
+    pub ph_grid_opacity: u8,
+    pub ph_cache_grid_size: i32,
+
+    // Path Viz
+    pub viz_color_to_home: (u8, u8, u8),
+    pub viz_color_to_food: (u8, u8, u8),
+    /// 路径显示的衰减方式
+    pub viz_decay_model: DecaySpec,
+    pub viz_decay_rate: f32,
+    pub viz_color_strength: f32,
+    pub viz_max_color_strength: f32,
+    pub viz_grid_opacity: u8,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            w: 1920.0,
+            h: 1080.0,
+            world_unbounded: false,
+            bg_color: (0, 0, 0),
+            seed: None,
+            map: None,
+            map_food_per_pixel: 500.0,
+            tick_hz: 60.0,
+            max_ticks_per_frame: 10,
+
+            colonies: vec![ColonySpec::default()],
+            home_sprite_scale: 2.5,
+            home_radius: 30.0,
+            ant_spawn_cost: 10.0,
+            ant_spawn_interval: 0.5,
+            max_ants: 10000,
+
+            food_sources: vec![FoodSpec::default()],
+            food_sprite_scale: 2.0,
+
+            obstacles: Vec::new(),
+            obstacle_color: (90, 90, 100),
+            terrain: Vec::new(),
+            terrain_color: (110, 90, 60),
+
+            num_ants: 5000,
+            ant_speed: 90.0,
+            ant_direction_randomness_deg: 300.0,
+            ant_direction_update_interval: 0.5,
+            ant_sprite_scale: 0.3,
+            ant_z_index: 3.0,
+            ant_initial_ph_strength: 32.0,
+            ant_ph_strength_decay_rate: 1.2,
+            ant_ph_strength_decay_interval: 0.5,
+            ant_ph_drop_interval: 0.7,
+            initial_ant_ph_scan_radius: 15.0,
+            ant_ph_scan_radius_increment: 0.1,
+            ant_ph_scan_radius_scale: 1.8,
+            ant_steering_force_factor: 0.7,
+            ant_steering: SteeringModel::Midpoint,
+            ant_antenna_angle_deg: 45.0,
+            ant_antenna_distance: 80.0,
+            ant_target_auto_pull_radius: 100.0,
+            ant_max_energy: 100.0,
+            ant_energy_drain_rate: 0.2,
+            ant_food_energy: 100.0,
+            ant_hunger_threshold: 0.2,
+            ant_lifespan: 600.0,
+            ant_lifespan_randomness: 0.2,
+
+            max_pheromone_strength: 500.0,
+            ph_decay_model: DecaySpec::Linear,
+            ph_decay_rate: 0.08,
+            ph_decay_interval: 0.1,
+            ph_diffusion_rate: 0.0,
+            ph_diffusion_loss: 0.0,
+            ph_img_update_sec: 0.1,
+            ph_grid_viz_min_strength: 1,
+            ph_unit_grid_size: 5,
+            ph_grid_backend: GridBackend::Sparse,
+            ph_grid_opacity: 255,
+            ph_cache_grid_size: 10,
+
+            viz_color_to_home: (17, 106, 123),
+            viz_color_to_food: (92, 46, 126),
+            viz_decay_model: DecaySpec::Linear,
+            viz_decay_rate: 0.3,
+            viz_color_strength: 8.0,
+            viz_max_color_strength: 50.0,
+            viz_grid_opacity: 255,
+        }
+    }
+}
+
+/// 一处食物：位置、拾取半径和总量（每只蚂蚁搬走 1）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FoodSpec {
+    pub location: (f32, f32),
+    pub radius: f32,
+    pub amount: f32,
+}
+
+impl Default for FoodSpec {
+    fn default() -> Self {
+        Self {
+            location: (-750.0, 400.0),
+            //location: (-379.5, 00.0),
+            radius: 30.0,
+            amount: 20000.0,
+        }
+    }
+}
+
+/// 一个蚁群：巢穴位置、蚂蚁颜色（可以大于 1 产生泛光）和它的信息素颜色
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColonySpec {
+    pub location: (f32, f32),
+    /// 找食物的蚂蚁的颜色
+    pub ant_color: (f32, f32, f32),
+    /// 带着食物回家的蚂蚁的颜色
+    pub ant_color_with_food: (f32, f32, f32),
+    pub ph_color_to_food: (u8, u8, u8),
+    pub ph_color_to_home: (u8, u8, u8),
+}
+
+impl Default for ColonySpec {
+    fn default() -> Self {
+        Self {
+            location: (759.0, -350.0),
+            ant_color: (1.0, 1.0, 2.5),
+            ant_color_with_food: (1.0, 2.0, 1.0),
+            ph_color_to_food: (2, 79, 2),
+            ph_color_to_home: (200, 81, 112),
+        }
+    }
+}
+
+/// 障碍物和地形的形状，坐标和世界坐标一致（原点在窗口中心）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeSpec {
+    Rect {
+        min: (f32, f32),
+        max: (f32, f32),
+    },
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+    /// 任意多边形，按顺序给出顶点
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl ShapeSpec {
+    /// 包围盒 (min, max)
+    pub fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        match self {
+            ShapeSpec::Rect { min, max } => (
+                (min.0.min(max.0), min.1.min(max.1)),
+                (min.0.max(max.0), min.1.max(max.1)),
+            ),
+            ShapeSpec::Circle { center, radius } => (
+                (center.0 - radius, center.1 - radius),
+                (center.0 + radius, center.1 + radius),
+            ),
+            ShapeSpec::Polygon(points) => points.iter().fold(
+                ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+                |(min, max), &(x, y)| ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+            ),
+        }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        match self {
+            ShapeSpec::Rect { min, max } => {
+                let (x0, x1) = (min.0.min(max.0), min.0.max(max.0));
+                let (y0, y1) = (min.1.min(max.1), min.1.max(max.1));
+                // 右边和上边不算在内，相邻的矩形不会重叠
+                x >= x0 && x < x1 && y >= y0 && y < y1
+            }
+            ShapeSpec::Circle { center, radius } => {
+                let (dx, dy) = (x - center.0, y - center.1);
+                dx * dx + dy * dy <= radius * radius
+            }
+            ShapeSpec::Polygon(points) => {
+                //射线法：向右的射线和边相交奇数次就在多边形内
+                let mut inside = false;
+                let mut j = points.len().wrapping_sub(1);
+                for (i, &(xi, yi)) in points.iter().enumerate() {
+                    let (xj, yj) = points[j];
+                    if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+        }
+    }
+}
+
+/// 蚂蚁的转向方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SteeringModel {
+    /// 朝扫描半径内所有信息素的加权中点转向
+    #[default]
+    Midpoint,
+    /// 左、中、右三根触角各读一处信息素，朝最强的一侧转
+    Antenna,
+}
+
+impl std::str::FromStr for SteeringModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "midpoint" => Ok(SteeringModel::Midpoint),
+            "antenna" => Ok(SteeringModel::Antenna),
+            _ => Err(format!(
+                "unknown steering model `{s}`, expected midpoint or antenna"
+            )),
+        }
+    }
+}
+
+/// 网格的存储方式，具体实现见 [`crate::store`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GridBackend {
+    /// HashMap，只存有信号的格子，适合很大但信号稀疏的世界；范围查询靠增量更新的空间索引
+    #[default]
+    Sparse,
+    /// 覆盖整个世界的数组，查找和范围查询都直接访问
+    Dense,
+    /// 64x64 格子的块，按需分配、清空后释放，可以用于没有边界的世界
+    Chunked,
+}
+
+/// 网格的衰减方式，具体实现见 [`crate::decay`]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DecaySpec {
+    /// 每次衰减减去衰减速度
+    #[default]
+    Linear,
+    /// 每次衰减蒸发掉衰减速度这个比例（不能大于 1）
+    Exponential,
+    /// 每隔这么多秒减半
+    HalfLife(f32),
+    /// 最后一次沉积后保持这么多秒，然后消失
+    TimeSinceDeposit(f32),
+}
+
+/// 一块难走的地形
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerrainSpec {
+    pub shape: ShapeSpec,
+    /// 速度系数，1 和平地一样
+    pub speed: f32,
+}
+
+/// 重新初始化信息素和蚂蚁，配置里需要重置的参数变化时触发
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct ResetSimulation;
+
+/// 命令行对配置的覆盖（`--seed`、`--map`、`--steering`），重新加载配置文件后要再应用一次
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    pub seed: Option<u64>,
+    pub map: Option<String>,
+    pub steering: Option<SteeringModel>,
+}
+
+impl ConfigOverrides {
+    /// 覆盖到配置上，设置了地图时重新生成巢穴、食物和墙并校验
+    pub fn apply(&self, config: &mut SimConfig) -> Result<(), ConfigError> {
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(steering) = self.steering {
+            config.ant_steering = steering;
+        }
+        if self.map.is_some() {
+            config.map = self.map.clone();
+            config.load_map()?;
+            config.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// 蚂蚁转向力随机系数的下限，`ant_steering_force_factor` 不能比它小
+pub const MIN_STEERING_FORCE_FACTOR: f32 = 0.4;
+
+impl SimConfig {
+    /// 从 RON 文件加载配置并校验
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path.as_ref()).map_err(ConfigError::Io)?;
+        Self::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, ConfigError> {
+        let mut config: SimConfig = ron::from_str(text).map_err(ConfigError::Parse)?;
+        config.load_map()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 设置了 `map` 时从 PNG 重新生成巢穴、食物、障碍物和地形
+    pub fn load_map(&mut self) -> Result<(), ConfigError> {
+        match self.map.clone() {
+            Some(path) => crate::map::apply_map(self, path),
+            None => Ok(()),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        positive("w", self.w)?;
+        positive("h", self.h)?;
+        positive("tick_hz", self.tick_hz)?;
+        if self.max_ticks_per_frame == 0 {
+            return Err(ConfigError::invalid("max_ticks_per_frame", "must be > 0"));
+        }
+
+        positive("home_radius", self.home_radius)?;
+        positive("map_food_per_pixel", self.map_food_per_pixel)?;
+        for obstacle in self.obstacles.iter() {
+            check_shape("obstacles", obstacle)?;
+        }
+        for terrain in self.terrain.iter() {
+            check_shape("terrain", &terrain.shape)?;
+            positive("terrain.speed", terrain.speed)?;
+        }
+        for colony in self.colonies.iter() {
+            self.check_in_world("colonies.location", colony.location)?;
+            self.check_not_blocked("colonies.location", colony.location)?;
+        }
+        positive("ant_spawn_cost", self.ant_spawn_cost)?;
+        positive("ant_spawn_interval", self.ant_spawn_interval)?;
+        for food in self.food_sources.iter() {
+            positive("food_sources.radius", food.radius)?;
+            positive("food_sources.amount", food.amount)?;
+            self.check_in_world("food_sources.location", food.location)?;
+            self.check_not_blocked("food_sources.location", food.location)?;
+        }
+
+        non_negative("ant_speed", self.ant_speed)?;
+        positive(
+            "ant_direction_update_interval",
+            self.ant_direction_update_interval,
+        )?;
+        non_negative("ant_initial_ph_strength", self.ant_initial_ph_strength)?;
+        non_negative(
+            "ant_ph_strength_decay_rate",
+            self.ant_ph_strength_decay_rate,
+        )?;
+        positive(
+            "ant_ph_strength_decay_interval",
+            self.ant_ph_strength_decay_interval,
+        )?;
+        positive("ant_ph_drop_interval", self.ant_ph_drop_interval)?;
+        positive(
+            "initial_ant_ph_scan_radius",
+            self.initial_ant_ph_scan_radius,
+        )?;
+        non_negative(
+            "ant_ph_scan_radius_increment",
+            self.ant_ph_scan_radius_increment,
+        )?;
+        positive("ant_ph_scan_radius_scale", self.ant_ph_scan_radius_scale)?;
+        let factor = self.ant_steering_force_factor;
+        if factor.is_nan() || factor < MIN_STEERING_FORCE_FACTOR {
+            return Err(ConfigError::invalid(
+                "ant_steering_force_factor",
+                format!("must be at least {MIN_STEERING_FORCE_FACTOR}, got {factor}"),
+            ));
+        }
+        if !(self.ant_antenna_angle_deg > 0.0 && self.ant_antenna_angle_deg < 180.0) {
+            return Err(ConfigError::invalid(
+                "ant_antenna_angle_deg",
+                "must be between 0 and 180",
+            ));
+        }
+        positive("ant_antenna_distance", self.ant_antenna_distance)?;
+        non_negative(
+            "ant_target_auto_pull_radius",
+            self.ant_target_auto_pull_radius,
+        )?;
+        positive("ant_max_energy", self.ant_max_energy)?;
+        non_negative("ant_energy_drain_rate", self.ant_energy_drain_rate)?;
+        positive("ant_food_energy", self.ant_food_energy)?;
+        if !(0.0..=1.0).contains(&self.ant_hunger_threshold) {
+            return Err(ConfigError::invalid(
+                "ant_hunger_threshold",
+                "must be between 0 and 1",
+            ));
+        }
+        positive("ant_lifespan", self.ant_lifespan)?;
+        if !(0.0..1.0).contains(&self.ant_lifespan_randomness) {
+            return Err(ConfigError::invalid(
+                "ant_lifespan_randomness",
+                "must be in [0, 1)",
+            ));
+        }
+
+        positive("max_pheromone_strength", self.max_pheromone_strength)?;
+        non_negative("ph_decay_rate", self.ph_decay_rate)?;
+        check_decay("ph_decay_model", self.ph_decay_model, self.ph_decay_rate)?;
+        positive("ph_decay_interval", self.ph_decay_interval)?;
+        for (field, value) in [
+            ("ph_diffusion_rate", self.ph_diffusion_rate),
+            ("ph_diffusion_loss", self.ph_diffusion_loss),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(ConfigError::invalid(field, "must be between 0 and 1"));
+            }
+        }
+        positive("ph_img_update_sec", self.ph_img_update_sec)?;
+        if self.ph_unit_grid_size == 0 {
+            return Err(ConfigError::invalid("ph_unit_grid_size", "must be > 0"));
+        }
+        if self.world_unbounded && self.ph_grid_backend == GridBackend::Dense {
+            return Err(ConfigError::invalid(
+                "ph_grid_backend",
+                "Dense needs a bounded world, use Chunked or Sparse",
+            ));
+        }
+        if self.ph_cache_grid_size <= 0 {
+            return Err(ConfigError::invalid("ph_cache_grid_size", "must be > 0"));
+        }
+
+        non_negative("viz_decay_rate", self.viz_decay_rate)?;
+        check_decay("viz_decay_model", self.viz_decay_model, self.viz_decay_rate)?;
+        non_negative("viz_color_strength", self.viz_color_strength)?;
+        positive("viz_max_color_strength", self.viz_max_color_strength)?;
+
+        Ok(())
+    }
+
+    fn check_in_world(&self, field: &'static str, (x, y): (f32, f32)) -> Result<(), ConfigError> {
+        if self.world_unbounded {
+            return Ok(());
+        }
+        let (hw, hh) = (self.w / 2.0, self.h / 2.0);
+        if x < -hw || x > hw || y < -hh || y > hh {
+            return Err(ConfigError::invalid(
+                field,
+                format!("({x}, {y}) is outside the {}x{} world", self.w, self.h),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_not_blocked(&self, field: &'static str, pos: (f32, f32)) -> Result<(), ConfigError> {
+        if self.obstacles.iter().any(|obstacle| obstacle.contains(pos)) {
+            return Err(ConfigError::invalid(
+                field,
+                format!("({}, {}) is inside an obstacle", pos.0, pos.1),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 新配置是否改动了只能在重置时生效的参数（蚂蚁数量、网格大小、巢穴和食物等）
+    pub fn needs_reset(&self, other: &SimConfig) -> bool {
+        self.w != other.w
+            || self.h != other.h
+            || self.world_unbounded != other.world_unbounded
+            || self.num_ants != other.num_ants
+            || self.colonies != other.colonies
+            || self.food_sources != other.food_sources
+            || self.obstacles != other.obstacles
+            || self.terrain != other.terrain
+            || self.max_pheromone_strength != other.max_pheromone_strength
+            || self.ph_unit_grid_size != other.ph_unit_grid_size
+            || self.ph_grid_backend != other.ph_grid_backend
+            || self.ph_cache_grid_size != other.ph_cache_grid_size
+            || self.viz_max_color_strength != other.viz_max_color_strength
+    }
+
+    /// 按配置生成信息素图层的衰减模型
+    pub fn ph_decay(&self) -> Box<dyn DecayModel> {
+        self.ph_decay_model.model(self.ph_decay_rate)
+    }
+
+    /// 按配置生成路径显示的衰减模型
+    pub fn viz_decay(&self) -> Box<dyn DecayModel> {
+        self.viz_decay_model.model(self.viz_decay_rate)
+    }
+
+    /// 渲染/网格图像的宽高（以格子为单位）
+    pub fn grid_img_size(&self) -> (usize, usize) {
+        (
+            self.w as usize / self.ph_unit_grid_size,
+            self.h as usize / self.ph_unit_grid_size,
+        )
+    }
+}
+
+fn check_shape(field: &'static str, shape: &ShapeSpec) -> Result<(), ConfigError> {
+    match shape {
+        ShapeSpec::Rect { .. } => Ok(()),
+        ShapeSpec::Circle { radius, .. } => positive(field, *radius),
+        ShapeSpec::Polygon(points) if points.len() < 3 => Err(ConfigError::invalid(
+            field,
+            "polygon needs at least 3 points",
+        )),
+        ShapeSpec::Polygon(_) => Ok(()),
+    }
+}
+
+fn positive(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::invalid(
+            field,
+            format!("must be > 0, got {value}"),
+        ))
+    }
+}
+
+fn check_decay(field: &'static str, spec: DecaySpec, rate: f32) -> Result<(), ConfigError> {
+    match spec {
+        DecaySpec::Linear => Ok(()),
+        DecaySpec::Exponential if rate <= 1.0 => Ok(()),
+        DecaySpec::Exponential => Err(ConfigError::invalid(
+            field,
+            format!("exponential decay needs a rate <= 1, got {rate}"),
+        )),
+        DecaySpec::HalfLife(secs) | DecaySpec::TimeSinceDeposit(secs) => positive(field, secs),
+    }
+}
+
+fn non_negative(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::invalid(
+            field,
+            format!("must be >= 0, got {value}"),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Map(image::ImageError),
+    Invalid { field: &'static str, reason: String },
+}
+
+impl ConfigError {
+    pub(crate) fn invalid(field: &'static str, reason: impl Into<String>) -> Self {
+        Self::Invalid {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "failed to parse config: {e}"),
+            ConfigError::Map(e) => write!(f, "failed to load map: {e}"),
+            ConfigError::Invalid { field, reason } => {
+                write!(f, "invalid config value `{field}`: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
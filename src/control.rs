@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    ecs::schedule::ScheduleLabel,
+    prelude::{
+        EventReader, IntoSystemConfigs, IntoSystemSetConfig, Plugin, Res, ResMut, Resource,
+        SystemSet, Time, Update, World,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ResetSimulation, SimConfig};
+
+/// 模拟的固定步长调度，每一步（tick）推进 `1 / tick_hz` 秒，和帧率无关
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimUpdate;
+
+/// 暂停/单步/倍速控制，决定每帧运行多少个 [`SimUpdate`]
+#[derive(Resource, Debug, Clone)]
+pub struct SimControl {
+    pub paused: bool,
+    pub step: bool,
+    pub speed: f32,
+    /// 设置后每帧固定运行这么多步，不看实际帧间隔（无窗口模式使用）
+    pub ticks_per_update: Option<u32>,
+    accumulator: f32,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            step: false,
+            speed: 1.0,
+            ticks_per_update: None,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl SimControl {
+    pub fn fixed_ticks(ticks_per_update: u32) -> Self {
+        Self {
+            ticks_per_update: Some(ticks_per_update),
+            ..Default::default()
+        }
+    }
+
+    /// 根据帧间隔算出本帧要运行的步数，单步请求在这里消耗掉
+    fn ticks_for_frame(&mut self, delta: f32, config: &SimConfig) -> u32 {
+        if self.step {
+            self.step = false;
+            return 1;
+        }
+        if self.paused {
+            return 0;
+        }
+        if let Some(ticks) = self.ticks_per_update {
+            return ticks;
+        }
+
+        let tick = 1.0 / config.tick_hz;
+        self.accumulator += delta * self.speed;
+        let ticks = (self.accumulator / tick) as u32;
+        if ticks > config.max_ticks_per_frame {
+            // 跟不上时丢掉积压的时间，避免越积越多
+            self.accumulator = 0.0;
+            config.max_ticks_per_frame
+        } else {
+            self.accumulator -= ticks as f32 * tick;
+            ticks
+        }
+    }
+}
+
+/// 模拟时钟：已经运行的步数和模拟时间（秒），定时条件都按它判断，快照里一起保存
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimClock {
+    pub tick: u64,
+    pub elapsed: f64,
+    /// 上一步的模拟时间
+    pub previous: f64,
+}
+
+impl SimClock {
+    fn advance(&mut self, config: &SimConfig) {
+        self.tick += 1;
+        self.previous = self.elapsed;
+        self.elapsed += 1.0 / config.tick_hz as f64;
+    }
+}
+
+/// 上一帧运行模拟用的步数和耗时（实际时间），性能图表用
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SimTiming {
+    pub ticks: u32,
+    pub duration: Duration,
+}
+
+impl SimTiming {
+    /// 平均每步的耗时（毫秒），这一帧没有运行时为 None
+    pub fn tick_ms(&self) -> Option<f64> {
+        (self.ticks > 0).then(|| self.duration.as_secs_f64() * 1000.0 / self.ticks as f64)
+    }
+}
+
+/// 模拟系统分组：先更新蚂蚁，再更新信息素
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimSet {
+    Ants,
+    Pheromones,
+}
+
+/// 运行 [`SimUpdate`] 的系统，重置等需要在模拟之前生效的系统排在它前面
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimTicks;
+
+/// 收到 [`ResetSimulation`] 后重建各自状态的系统，都在 [`SimTicks`] 之前；
+/// 修改配置、发出重置的系统（重新加载配置、编辑器）排在这一组之前，同一帧里各部分按同一份配置重建
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimReset;
+
+pub struct SimControlPlugin;
+
+impl Plugin for SimControlPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if app.get_schedule(SimUpdate).is_none() {
+            app.init_schedule(SimUpdate);
+        }
+
+        app.init_resource::<SimConfig>()
+            .init_resource::<SimControl>()
+            .init_resource::<SimClock>()
+            .init_resource::<SimTiming>()
+            .add_event::<ResetSimulation>()
+            .configure_set(SimUpdate, SimSet::Pheromones.after(SimSet::Ants))
+            .configure_set(Update, SimReset.before(SimTicks))
+            .add_systems(Update, reset_clock.in_set(SimReset))
+            .add_systems(Update, run_sim_ticks.in_set(SimTicks));
+    }
+}
+
+fn run_sim_ticks(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds();
+    let config = world.resource::<SimConfig>().clone();
+    let ticks = world
+        .resource_mut::<SimControl>()
+        .ticks_for_frame(delta, &config);
+
+    let start = Instant::now();
+    for _ in 0..ticks {
+        world.resource_mut::<SimClock>().advance(&config);
+        world.run_schedule(SimUpdate);
+    }
+    *world.resource_mut::<SimTiming>() = SimTiming {
+        ticks,
+        duration: start.elapsed(),
+    };
+}
+
+fn reset_clock(mut reset_events: EventReader<ResetSimulation>, mut clock: ResMut<SimClock>) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    *clock = SimClock::default();
+}
+
+/// 按模拟时间触发的定时条件：[`SimClock`] 在这一步跨过 interval 的整数倍时为真；
+/// 不保存状态，恢复快照后照样接着触发；间隔每次从 [`SimConfig`] 读取，修改配置后立即生效
+pub fn sim_timer(
+    interval: fn(&SimConfig) -> f32,
+) -> impl Fn(Res<SimConfig>, Res<SimClock>) -> bool + Clone {
+    move |config: Res<SimConfig>, clock: Res<SimClock>| {
+        let interval = interval(&config) as f64;
+        (clock.elapsed / interval).floor() != (clock.previous / interval).floor()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::DecaySpec;
+
+/// 按比例衰减永远到不了 0，低于这个值就当作没有了
+const MIN_SIGNAL: f32 = 0.01;
+
+fn cut_off(value: f32) -> f32 {
+    if value < MIN_SIGNAL {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// 信号的衰减方式，每个 [`crate::grids::DecayGrid`] 各自持有一个
+pub trait DecayModel: Send + Sync {
+    /// 衰减一次后的值：dt 是两次衰减之间的秒数，age 是这个格子上次沉积以来的秒数
+    fn decay(&self, value: f32, age: f32, dt: f32) -> f32;
+
+    /// 是否需要记录每个格子上次沉积以来的时间，不需要时网格不记
+    fn uses_age(&self) -> bool {
+        false
+    }
+}
+
+/// 每次衰减减去固定的量
+#[derive(Debug, Clone, Copy)]
+pub struct LinearDecay(pub f32);
+
+/// 每次衰减蒸发掉固定的比例，即 ACO 里的 v *= 1 - ρ
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialDecay(pub f32);
+
+/// 信号每隔固定秒数减半，和衰减间隔无关
+#[derive(Debug, Clone, Copy)]
+pub struct HalfLifeDecay(pub f32);
+
+/// 最后一次沉积后保持不变，过了固定秒数一下子消失
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSinceDepositDecay(pub f32);
+
+impl DecayModel for LinearDecay {
+    fn decay(&self, value: f32, _age: f32, _dt: f32) -> f32 {
+        f32::max(value - self.0, 0.0)
+    }
+}
+
+impl DecayModel for ExponentialDecay {
+    fn decay(&self, value: f32, _age: f32, _dt: f32) -> f32 {
+        cut_off(value * (1.0 - self.0))
+    }
+}
+
+impl DecayModel for HalfLifeDecay {
+    fn decay(&self, value: f32, _age: f32, dt: f32) -> f32 {
+        cut_off(value * 0.5f32.powf(dt / self.0))
+    }
+}
+
+impl DecayModel for TimeSinceDepositDecay {
+    fn decay(&self, value: f32, age: f32, _dt: f32) -> f32 {
+        if age >= self.0 {
+            0.0
+        } else {
+            value
+        }
+    }
+
+    fn uses_age(&self) -> bool {
+        true
+    }
+}
+
+impl DecaySpec {
+    /// rate 是配置里的衰减速度（`ph_decay_rate` / `viz_decay_rate`），只有线性和指数衰减用到
+    pub fn model(&self, rate: f32) -> Box<dyn DecayModel> {
+        match *self {
+            DecaySpec::Linear => Box::new(LinearDecay(rate)),
+            DecaySpec::Exponential => Box::new(ExponentialDecay(rate)),
+            DecaySpec::HalfLife(secs) => Box::new(HalfLifeDecay(secs)),
+            DecaySpec::TimeSinceDeposit(secs) => Box::new(TimeSinceDepositDecay(secs)),
+        }
+    }
+}
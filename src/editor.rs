@@ -0,0 +1,234 @@
+use bevy::{
+    prelude::{
+        Camera, Commands, GlobalTransform, Input, IntoSystemConfigs, KeyCode, MouseButton, Plugin,
+        Query, Res, ResMut, Resource, Transform, Update, Vec2, With,
+    },
+    window::{PrimaryWindow, Window},
+};
+use bevy_egui::{
+    egui::{self, Slider},
+    EguiContexts, EguiPlugin,
+};
+use bevy_pancam::PanCam;
+
+use crate::{
+    colony::Colony, control::SimReset, food::FoodSource, obstacle::Obstacles,
+    pheromone::Pheromones, FoodSpec, SimConfig,
+};
+
+/// 编辑器画笔：左键画，右键擦
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Brush {
+    Walls,
+    ToFood,
+    ToHome,
+}
+
+/// 世界编辑器，按 E 开关；编辑直接作用在正在运行的模拟上，重置模拟时按配置重建，编辑都会丢掉
+#[derive(Resource, Debug, Clone)]
+pub struct WorldEditor {
+    pub enabled: bool,
+    pub brush: Brush,
+    pub brush_radius: f32,
+    /// 信息素画笔写入的强度
+    pub brush_strength: f32,
+    /// 信息素画笔和移动巢穴作用的蚁群
+    pub colony: usize,
+}
+
+impl Default for WorldEditor {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brush: Brush::Walls,
+            brush_radius: 15.0,
+            brush_strength: 100.0,
+            colony: 0,
+        }
+    }
+}
+
+/// 鼠标画墙、擦墙，快捷键放食物和移动巢穴，画笔直接修改信息素图层
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<WorldEditor>().add_systems(
+            Update,
+            (editor_hotkeys, editor_paint, editor_window)
+                .chain()
+                .before(SimReset),
+        );
+    }
+}
+
+/// 光标所在的世界坐标
+fn cursor_world_pos(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = window_query.get_single().ok()?.cursor_position()?;
+    let (camera, transform) = camera_query.get_single().ok()?;
+    camera.viewport_to_world_2d(transform, cursor)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn editor_hotkeys(
+    mut commands: Commands,
+    mut editor: ResMut<WorldEditor>,
+    mut contexts: EguiContexts,
+    mut pancam_query: Query<&mut PanCam>,
+    mut colony_query: Query<(&mut Colony, Option<&mut Transform>)>,
+    keys: Res<Input<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    pheromones: Res<Pheromones>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::E) {
+        editor.enabled = !editor.enabled;
+        //编辑时左右键留给画笔，只用中键拖动镜头
+        for mut pancam in pancam_query.iter_mut() {
+            pancam.grab_buttons = if editor.enabled {
+                vec![MouseButton::Middle]
+            } else {
+                PanCam::default().grab_buttons
+            };
+        }
+    }
+    if !editor.enabled {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Key1) {
+        editor.brush = Brush::Walls;
+    }
+    if keys.just_pressed(KeyCode::Key2) {
+        editor.brush = Brush::ToFood;
+    }
+    if keys.just_pressed(KeyCode::Key3) {
+        editor.brush = Brush::ToHome;
+    }
+    if keys.just_pressed(KeyCode::Tab) && !pheromones.colonies.is_empty() {
+        editor.colony = (editor.colony + 1) % pheromones.colonies.len();
+    }
+
+    let Some(pos) = cursor_world_pos(&window_query, &camera_query) else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::F) {
+        let food = FoodSpec::default();
+        commands.spawn(FoodSource {
+            pos,
+            radius: food.radius,
+            amount: food.amount,
+            initial_amount: food.amount,
+        });
+    }
+    //巢穴和信息素图层一一对应，只移动选中蚁群的巢穴，不另建一个
+    if keys.just_pressed(KeyCode::N) {
+        let selected = colony_query
+            .iter_mut()
+            .find(|(colony, _)| colony.id.0 == editor.colony);
+        if let Some((mut colony, transform)) = selected {
+            colony.pos = pos;
+            if let Some(mut transform) = transform {
+                transform.translation.x = pos.x;
+                transform.translation.y = pos.y;
+            }
+        }
+    }
+}
+
+fn editor_paint(
+    editor: Res<WorldEditor>,
+    mut contexts: EguiContexts,
+    mouse: Res<Input<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut obstacles: ResMut<Obstacles>,
+    mut pheromones: ResMut<Pheromones>,
+) {
+    if !editor.enabled || contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let (paint, erase) = (
+        mouse.pressed(MouseButton::Left),
+        mouse.pressed(MouseButton::Right),
+    );
+    if !paint && !erase {
+        return;
+    }
+    let Some(pos) = cursor_world_pos(&window_query, &camera_query) else {
+        return;
+    };
+
+    let cells = obstacles.cells_in_radius(pos, editor.brush_radius);
+    if editor.brush == Brush::Walls {
+        for key in cells {
+            if paint {
+                obstacles.block(key);
+            } else {
+                obstacles.unblock(&key);
+            }
+        }
+        return;
+    }
+
+    let Some(layers) = pheromones.colonies.get_mut(editor.colony) else {
+        return;
+    };
+    let grid = match editor.brush {
+        Brush::ToFood => &mut layers.to_food,
+        _ => &mut layers.to_home,
+    };
+    for key in cells {
+        if paint {
+            grid.set_signal(&key, editor.brush_strength);
+        } else {
+            grid.remove_signal(&key);
+        }
+    }
+}
+
+fn editor_window(
+    mut contexts: EguiContexts,
+    mut editor: ResMut<WorldEditor>,
+    pheromones: Res<Pheromones>,
+    config: Res<SimConfig>,
+) {
+    if !editor.enabled {
+        return;
+    }
+
+    egui::Window::new("Editor").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut editor.brush, Brush::Walls, "1 walls");
+            ui.selectable_value(&mut editor.brush, Brush::ToFood, "2 to_food");
+            ui.selectable_value(&mut editor.brush, Brush::ToHome, "3 to_home");
+        });
+        ui.add(Slider::new(&mut editor.brush_radius, 1.0..=100.0).text("brush radius"));
+        ui.add(
+            Slider::new(
+                &mut editor.brush_strength,
+                1.0..=config.max_pheromone_strength,
+            )
+            .text("pheromone strength"),
+        );
+        if !pheromones.colonies.is_empty() {
+            let max = pheromones.colonies.len() - 1;
+            ui.add(Slider::new(&mut editor.colony, 0..=max).text("colony (Tab)"));
+        }
+        ui.separator();
+        ui.label("left drag: paint, right drag: erase, middle drag: pan");
+        ui.label("F: place food, N: move nest here, E: leave editor");
+        ui.label("edits are lost when the simulation resets");
+    });
+}
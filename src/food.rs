@@ -0,0 +1,132 @@
+use bevy::{
+    math::vec2,
+    prelude::{
+        Added, AssetServer, Changed, Color, Commands, Component, Entity, EventReader,
+        IntoSystemConfigs, Plugin, Query, Res, Startup, Transform, Update, Vec2, Vec3, With,
+    },
+    sprite::{Sprite, SpriteBundle},
+};
+
+use crate::{
+    control::{SimControlPlugin, SimReset, SimSet, SimUpdate},
+    ResetSimulation, SimConfig, SPRITE_FOOD,
+};
+
+/// 食物源，蚂蚁每次拾取搬走 1，搬完后消失
+#[derive(Component, Debug, Clone)]
+pub struct FoodSource {
+    pub pos: Vec2,
+    pub radius: f32,
+    pub amount: f32,
+    pub initial_amount: f32,
+}
+
+impl FoodSource {
+    pub fn is_depleted(&self) -> bool {
+        self.amount <= 0.0
+    }
+
+    /// 拾取一份食物，已经搬完时返回 false
+    pub fn take(&mut self) -> bool {
+        if self.is_depleted() {
+            return false;
+        }
+        self.amount -= 1.0;
+        true
+    }
+}
+
+pub struct FoodPlugin;
+
+/// 食物贴图，无窗口模式下不添加
+pub struct FoodRenderPlugin;
+
+impl Plugin for FoodPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+
+        app.add_systems(Startup, setup)
+            .add_event::<ResetSimulation>()
+            .add_systems(Update, reset_food.in_set(SimReset))
+            .add_systems(SimUpdate, remove_depleted_food.after(SimSet::Ants));
+    }
+}
+
+impl Plugin for FoodRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, (attach_food_sprites, update_food_sprites));
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
+    spawn_food(&mut commands, &config);
+}
+
+fn spawn_food(commands: &mut Commands, config: &SimConfig) {
+    for food in config.food_sources.iter() {
+        commands.spawn(FoodSource {
+            pos: vec2(food.location.0, food.location.1),
+            radius: food.radius,
+            amount: food.amount,
+            initial_amount: food.amount,
+        });
+    }
+}
+
+fn reset_food(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetSimulation>,
+    food_query: Query<Entity, With<FoodSource>>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    for entity in food_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_food(&mut commands, &config);
+}
+
+fn remove_depleted_food(mut commands: Commands, food_query: Query<(Entity, &FoodSource)>) {
+    for (entity, food) in food_query.iter() {
+        if food.is_depleted() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn attach_food_sprites(
+    mut commands: Commands,
+    food_query: Query<(Entity, &FoodSource), Added<FoodSource>>,
+    assert_server: Res<AssetServer>,
+    config: Res<SimConfig>,
+) {
+    for (entity, food) in food_query.iter() {
+        commands.entity(entity).insert(SpriteBundle {
+            texture: assert_server.load(SPRITE_FOOD),
+            sprite: Sprite {
+                color: Color::rgb(1.5, 1.5, 1.5),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(food.pos.x, food.pos.y, 2.0)
+                .with_scale(Vec3::splat(config.food_sprite_scale)),
+            ..Default::default()
+        });
+    }
+}
+
+///剩余越少贴图越小
+fn update_food_sprites(
+    mut food_query: Query<(&FoodSource, &mut Transform), Changed<FoodSource>>,
+    config: Res<SimConfig>,
+) {
+    for (food, mut transform) in food_query.iter_mut() {
+        let remaining = (food.amount / food.initial_amount).clamp(0.0, 1.0);
+        transform.scale = Vec3::splat(config.food_sprite_scale * (0.3 + 0.7 * remaining));
+    }
+}
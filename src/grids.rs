@@ -1,28 +1,57 @@
 use std::{cmp, collections::HashMap};
 
-use bevy::prelude::{Vec2, Vec3};
-use kd_tree::KdTree;
+use bevy::{
+    math::vec2,
+    prelude::{Vec2, Vec3},
+};
 
 use crate::{
+    decay::DecayModel,
+    obstacle::Obstacles,
+    store::{SignalStore, SparseStore, CHUNK_SIZE},
     utils::{calc_weighted_midpoint, window_to_grid},
-    H, MAX_PHEROMONE_STRENGTH, PH_CACHE_GRID_SIZE, PH_DECAY_RATE, PH_GRID_OPACITY,
-    PH_GRID_VIZ_MIN_STRENGTH, PH_UNIT_GRID_SIZE, W,
+    SimConfig,
 };
 
 pub struct WorldGrid {
     pub color: (u8, u8, u8),
     signals: DecayGrid,
-    tree: Option<KdTree<[f32; 2]>>,
     steer_cache: HashMap<(i32, i32), Vec2>,
+    /// 上次统计以来转向缓存命中/未命中的次数
+    steer_hits: u32,
+    steer_misses: u32,
+    unit_grid_size: i32,
+    cache_grid_size: i32,
+    half_size: (i32, i32),
 }
 
 impl WorldGrid {
-    pub fn new(color: (u8, u8, u8), signals: HashMap<(i32, i32), f32>) -> Self {
+    /// 存储方式按 `ph_grid_backend`，稠密存储覆盖整个世界
+    pub fn new(color: (u8, u8, u8), signals: HashMap<(i32, i32), f32>, config: &SimConfig) -> Self {
+        let unit = config.ph_unit_grid_size as i32;
+        let (max_x, max_y) = (
+            (config.w / 2.0) as i32 / unit + 1,
+            (config.h / 2.0) as i32 / unit + 1,
+        );
+        let mut store = config
+            .ph_grid_backend
+            .store((-max_x, -max_y), (max_x, max_y));
+        for (key, value) in signals {
+            store.insert(key, value);
+        }
+
         Self {
             color,
-            signals: DecayGrid::new(signals, MAX_PHEROMONE_STRENGTH),
-            tree: None,
+            signals: DecayGrid::with_store(store, config.max_pheromone_strength, config.ph_decay()),
             steer_cache: HashMap::new(),
+            steer_hits: 0,
+            steer_misses: 0,
+            unit_grid_size: config.ph_unit_grid_size as i32,
+            cache_grid_size: config.ph_cache_grid_size,
+            half_size: (
+                (config.w as usize / 2) as i32,
+                (config.h as usize / 2) as i32,
+            ),
         }
     }
 
@@ -34,86 +63,167 @@ impl WorldGrid {
         self.signals.add_value(key, value, value * 0.25)
     }
 
+    /// 直接设置格子上的信号（编辑器画笔用），key 是格子坐标
+    pub fn set_signal(&mut self, key: &(i32, i32), value: f32) {
+        self.signals.set_value(key, value);
+    }
+
+    pub fn remove_signal(&mut self, key: &(i32, i32)) {
+        self.signals.remove_value(key);
+    }
+
+    /// 向下取整，格子 k 覆盖 [k * unit, (k + 1) * unit)，负坐标的格子和正坐标一样大
     fn get_ph_key(&self, x: i32, y: i32) -> (i32, i32) {
-        (x / PH_UNIT_GRID_SIZE as i32, y / PH_UNIT_GRID_SIZE as i32)
+        (
+            x.div_euclid(self.unit_grid_size),
+            y.div_euclid(self.unit_grid_size),
+        )
     }
 
-    ///获取转向目标
-    pub fn get_steer_target(&mut self, pos: &Vec3, radius: f32) -> Option<Vec2> {
+    ///获取转向目标，墙里和墙后看不到的信号不算，路径不能穿墙
+    pub fn get_steer_target(
+        &mut self,
+        pos: &Vec3,
+        radius: f32,
+        obstacles: &Obstacles,
+    ) -> Option<Vec2> {
         let (x, y) = (pos.x as i32, pos.y as i32);
         let grid_pos = self.get_cache_grid_pos(x, y);
         if let Some(v) = self.steer_cache.get(&grid_pos) {
-            return Some(*v);
+            //缓存格子可能跨墙，看不到缓存的目标时重新计算
+            if obstacles.is_visible(pos.truncate(), *v) {
+                self.steer_hits += 1;
+                return Some(*v);
+            }
         }
-        match self.get_ph_in_range(pos, radius) {
-            Some(v) => {
-                if v.is_empty() {
-                    return None;
-                }
+        self.steer_misses += 1;
+        let mut v = self.get_ph_in_range(pos, radius);
+        let key = self.get_ph_key(pos.x.floor() as i32, pos.y.floor() as i32);
+        // 附近没有墙时所有信号都看得到，不用逐个做视线检查；有墙时先去掉墙里的格子
+        if obstacles.blocks_near(key, radius) {
+            let unit = self.unit_grid_size;
+            v.retain(|(x, y, _)| {
+                !obstacles.is_blocked_cell(&(x.div_euclid(unit), y.div_euclid(unit)))
+                    && obstacles.is_visible(pos.truncate(), vec2(*x as f32, *y as f32))
+            });
+        }
+        if v.is_empty() {
+            return None;
+        }
+
+        let steer_target = calc_weighted_midpoint(&v);
+        self.steer_cache.insert(grid_pos, steer_target);
+        Some(steer_target)
+    }
 
-                let steer_target = calc_weighted_midpoint(&v);
-                self.steer_cache.insert(grid_pos, steer_target);
-                Some(steer_target)
+    /// 触角取样：pos（世界坐标）所在格子和周围一圈格子的信号之和
+    pub fn sample(&self, pos: Vec2) -> f32 {
+        let (kx, ky) = self.get_ph_key(pos.x.floor() as i32, pos.y.floor() as i32);
+        let mut total = 0.0;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                total += self.signals.get(&(kx + dx, ky + dy)).unwrap_or(0.0);
             }
-            None => None,
         }
+        total
     }
 
     fn get_pos_from_ph(&self, x: i32, y: i32) -> (i32, i32) {
-        (x * PH_UNIT_GRID_SIZE as i32, y * PH_UNIT_GRID_SIZE as i32)
+        (x * self.unit_grid_size, y * self.unit_grid_size)
     }
 
     //取一个模糊点的来提取信号素？
     fn get_cache_grid_pos(&self, x: i32, y: i32) -> (i32, i32) {
-        let (tx, ty) = (x + (W as usize / 2) as i32, (H as usize / 2) as i32 - y);
-        let (tx, ty) = (tx / PH_CACHE_GRID_SIZE, ty / PH_CACHE_GRID_SIZE);
+        let (tx, ty) = (x + self.half_size.0, self.half_size.1 - y);
+        let (tx, ty) = (
+            tx.div_euclid(self.cache_grid_size),
+            ty.div_euclid(self.cache_grid_size),
+        );
 
         (tx, ty)
     }
 
-    //以pos为中心点，取半径为radius范围内的信息素
-    fn get_ph_in_range(&self, pos: &Vec3, radius: f32) -> Option<Vec<(i32, i32, f32)>> {
-        let key = self.get_ph_key(pos.x as i32, pos.y as i32);
-        if let Some(t) = &self.tree {
-            let mut ph_items = Vec::new();
-            let found = t.within_radius(&[key.0 as f32, key.1 as f32], radius);
-            for i in found.iter() {
-                let [x, y] = *i;
-                let (x, y) = (*x as i32, *y as i32);
-                if let Some(v) = self.signals.values.get(&(x, y)) {
-                    let world_xy = self.get_pos_from_ph(x, y);
-                    ph_items.push((world_xy.0, world_xy.1, *v));
-                }
-            }
-            Some(ph_items)
-        } else {
-            None
-        }
+    //以pos为中心点，取半径为radius范围内的信息素，直接读当前的信号
+    fn get_ph_in_range(&self, pos: &Vec3, radius: f32) -> Vec<(i32, i32, f32)> {
+        let key = self.get_ph_key(pos.x.floor() as i32, pos.y.floor() as i32);
+        let mut found = self.signals.store().cells_in_radius(key, radius);
+        // 存储的遍历顺序不固定，排序后加权求和的结果才可复现
+        found.sort_unstable_by_key(|(k, _)| *k);
+        found
+            .into_iter()
+            .map(|((x, y), v)| {
+                let world_xy = self.get_pos_from_ph(x, y);
+                (world_xy.0, world_xy.1, v)
+            })
+            .collect()
     }
 
-    pub fn update_tree(&mut self) {
-        let mut pts = Vec::new();
-        for (k, &v) in self.signals.values.iter() {
-            if v <= 0.0 {
-                continue;
-            }
+    /// 转向缓存（按格子排好序），缓存会影响之后的转向，快照要一起保存
+    pub fn steer_cache_entries(&self) -> Vec<((i32, i32), Vec2)> {
+        let mut entries: Vec<_> = self.steer_cache.iter().map(|(k, v)| (*k, *v)).collect();
+        entries.sort_by_key(|(k, _)| *k);
+        entries
+    }
 
-            let (x, y) = *k;
-            pts.push([x as f32, y as f32]);
-        }
-        self.tree = Some(KdTree::build_by_ordered_float(pts));
+    pub fn restore_steer_cache(&mut self, entries: impl IntoIterator<Item = ((i32, i32), Vec2)>) {
+        self.steer_cache = entries.into_iter().collect();
+    }
+
+    /// 衰减一次，dt 是衰减间隔（秒）
+    pub fn decay_signals(&mut self, dt: f32) {
+        self.signals.decay_values(dt);
     }
 
-    pub fn decay_signals(&mut self) {
-        self.signals.decay_values(PH_DECAY_RATE);
+    pub fn set_decay_model(&mut self, model: Box<dyn DecayModel>) {
+        self.signals.set_model(model);
+    }
+
+    /// 各格子上次沉积以来的秒数（按格子排好序），衰减模型不需要时为空
+    pub fn signal_ages(&self) -> Vec<((i32, i32), f32)> {
+        self.signals.ages()
+    }
+
+    pub fn restore_signal_ages(&mut self, ages: impl IntoIterator<Item = ((i32, i32), f32)>) {
+        self.signals.restore_ages(ages);
+    }
+
+    /// 扩散一步，见 [`DecayGrid::diffuse`]
+    pub fn diffuse_signals(&mut self, rate: f32, loss: f32, blocked: impl Fn(&(i32, i32)) -> bool) {
+        self.signals.diffuse(rate, loss, blocked);
     }
 
     pub fn drop_zero_signals(&mut self) {
         self.signals.drop_zero_values();
     }
 
-    pub fn get_signals(&self) -> &HashMap<(i32, i32), f32> {
-        self.signals.get_values()
+    /// 所有有信号的格子（格子坐标）
+    pub fn signals(&self) -> impl Iterator<Item = ((i32, i32), f32)> + '_ {
+        self.signals.cells()
+    }
+
+    pub fn signal_count(&self) -> usize {
+        self.signals.len()
+    }
+
+    /// 区域查询索引的大小，见 [`SignalStore::index_size`]
+    pub fn index_size(&self) -> usize {
+        self.signals.store().index_size()
+    }
+
+    /// 分块存储的已加载块，见 [`SignalStore::chunks`]
+    pub fn chunks(&self) -> Option<impl Iterator<Item = ((i32, i32), &[f32])> + '_> {
+        self.signals.store().chunks()
+    }
+
+    pub fn total_strength(&self) -> f32 {
+        self.signals.cells().map(|(_, v)| v).sum()
+    }
+
+    /// 取出并清零转向缓存的 (命中, 未命中) 次数
+    pub fn take_steer_stats(&mut self) -> (u32, u32) {
+        let stats = (self.steer_hits, self.steer_misses);
+        (self.steer_hits, self.steer_misses) = (0, 0);
+        stats
     }
 
     pub fn clear_steer_cache(&mut self) -> u32 {
@@ -124,16 +234,71 @@ impl WorldGrid {
     }
 }
 
+/// 3x3 核里周围的 8 个格子
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// 分给每个邻居的信号比这个还小的格子不扩散，免得整张图都长满微弱的信号
+const MIN_DIFFUSION_SHARE: f32 = 0.01;
+
 pub struct DecayGrid {
     max_allowed_value: f32,
-    values: HashMap<(i32, i32), f32>,
+    values: Box<dyn SignalStore>,
+    model: Box<dyn DecayModel>,
+    /// 各格子上次沉积以来的秒数，只有衰减模型需要时才记
+    ages: HashMap<(i32, i32), f32>,
 }
 
 impl DecayGrid {
-    pub fn new(values: HashMap<(i32, i32), f32>, max_allowed_value: f32) -> Self {
+    /// 用 HashMap 存储
+    pub fn new(
+        values: HashMap<(i32, i32), f32>,
+        max_allowed_value: f32,
+        model: Box<dyn DecayModel>,
+    ) -> Self {
+        Self::with_store(
+            Box::new(SparseStore::from(values)),
+            max_allowed_value,
+            model,
+        )
+    }
+
+    pub fn with_store(
+        values: Box<dyn SignalStore>,
+        max_allowed_value: f32,
+        model: Box<dyn DecayModel>,
+    ) -> Self {
         Self {
             values,
             max_allowed_value,
+            model,
+            ages: HashMap::new(),
+        }
+    }
+
+    pub fn store(&self) -> &dyn SignalStore {
+        self.values.as_ref()
+    }
+
+    /// 换一个衰减模型，已有的信号保留；新模型不需要格子的时间时清掉
+    pub fn set_model(&mut self, model: Box<dyn DecayModel>) {
+        if !model.uses_age() {
+            self.ages.clear();
+        }
+        self.model = model;
+    }
+
+    fn mark_deposit(&mut self, key: &(i32, i32)) {
+        if self.model.uses_age() {
+            self.ages.insert(*key, 0.0);
         }
     }
 
@@ -150,52 +315,276 @@ impl DecayGrid {
                 self.values.insert(*key, value);
             }
         }
+        self.mark_deposit(key);
+    }
+
+    pub fn set_value(&mut self, key: &(i32, i32), value: f32) {
+        self.values
+            .insert(*key, value.clamp(0.0, self.max_allowed_value));
+        self.mark_deposit(key);
+    }
+
+    pub fn remove_value(&mut self, key: &(i32, i32)) {
+        self.values.remove(key);
+        self.ages.remove(key);
+    }
+
+    /// 按衰减模型衰减一次，dt 是衰减间隔（秒）
+    pub fn decay_values(&mut self, dt: f32) {
+        let model = &self.model;
+        if !model.uses_age() {
+            self.values
+                .for_each_mut(&mut |_, v| *v = model.decay(*v, 0.0, dt));
+            return;
+        }
+
+        // 扩散出来的格子没有沉积过，从第一次衰减开始计时
+        let ages = &mut self.ages;
+        self.values.for_each_mut(&mut |key, v| {
+            let age = ages.entry(key).or_insert(0.0);
+            *age += dt;
+            *v = model.decay(*v, *age, dt);
+        });
     }
 
-    pub fn decay_values(&mut self, decay_rate: f32) {
-        for (_, v) in self.values.iter_mut() {
-            *v = f32::max(*v - decay_rate, 0.0);
+    /// 格子的时间（按格子排好序），快照要一起保存
+    pub fn ages(&self) -> Vec<((i32, i32), f32)> {
+        let mut ages: Vec<_> = self.ages.iter().map(|(k, v)| (*k, *v)).collect();
+        ages.sort_by_key(|(k, _)| *k);
+        ages
+    }
+
+    pub fn restore_ages(&mut self, ages: impl IntoIterator<Item = ((i32, i32), f32)>) {
+        self.ages = ages.into_iter().collect();
+    }
+
+    /// 3x3 核扩散：每个格子把 rate 比例的信号平分给周围 8 个格子，分到墙里（blocked）的份额留在原地；
+    /// 扩散出去的信号损失 loss 比例，0 时总量守恒（超过上限的部分除外）
+    pub fn diffuse(&mut self, rate: f32, loss: f32, blocked: impl Fn(&(i32, i32)) -> bool) {
+        if rate <= 0.0 {
+            return;
+        }
+
+        // 每个格子留下的量和分给每个邻居的量
+        let mut outflow: HashMap<(i32, i32), (f32, f32)> = HashMap::new();
+        for (key, value) in self.values.cells() {
+            let share = value * rate / NEIGHBOURS.len() as f32;
+            if share < MIN_DIFFUSION_SHARE {
+                outflow.insert(key, (value, 0.0));
+                continue;
+            }
+            let open = NEIGHBOURS
+                .iter()
+                .filter(|(dx, dy)| !blocked(&(key.0 + dx, key.1 + dy)))
+                .count();
+            outflow.insert(key, (value - share * open as f32, share));
+        }
+
+        // 按固定的邻居顺序累加，结果和遍历顺序无关
+        let mut values = HashMap::with_capacity(outflow.len());
+        for (&key, &(_, share)) in outflow.iter() {
+            for (dx, dy) in NEIGHBOURS.iter().chain([(0, 0)].iter()) {
+                let target = (key.0 + dx, key.1 + dy);
+                if (share <= 0.0 && target != key) || values.contains_key(&target) {
+                    continue;
+                }
+                if target != key && blocked(&target) {
+                    continue;
+                }
+                let kept = outflow.get(&target).map_or(0.0, |(kept, _)| *kept);
+                let received: f32 = NEIGHBOURS
+                    .iter()
+                    .filter_map(|(dx, dy)| outflow.get(&(target.0 - dx, target.1 - dy)))
+                    .map(|(_, share)| share)
+                    .sum();
+                let value = kept + received * (1.0 - loss);
+                values.insert(target, value.min(self.max_allowed_value));
+            }
+        }
+        self.values.clear();
+        for (key, value) in values {
+            self.values.insert(key, value);
         }
     }
 
     pub fn drop_zero_values(&mut self) {
-        self.values.retain(|_, v| *v > 0.0);
+        self.values.retain(&mut |_, v| v > 0.0);
+        if !self.ages.is_empty() {
+            let values = &self.values;
+            self.ages.retain(|key, _| values.get(key).is_some());
+        }
     }
 
-    pub fn get_values(&self) -> &HashMap<(i32, i32), f32> {
-        &self.values
+    pub fn get(&self, key: &(i32, i32)) -> Option<f32> {
+        self.values.get(key)
+    }
+
+    /// 有信号的格子数
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// 所有有信号的格子
+    pub fn cells(&self) -> impl Iterator<Item = ((i32, i32), f32)> + '_ {
+        self.values.cells()
     }
 }
 
 pub fn add_map_to_grid_img(
-    map: &HashMap<(i32, i32), f32>,
+    map: impl IntoIterator<Item = ((i32, i32), f32)>,
     color: &(u8, u8, u8),
-    img_bytes: &mut Vec<u8>,
+    img_bytes: &mut [u8],
     use_grid_pos: bool,
+    opacity: u8,
+    config: &SimConfig,
 ) {
-    let w = W as usize / PH_UNIT_GRID_SIZE;
-    for (k, v) in map.iter() {
+    let (w, h) = config.grid_img_size();
+    let unit = config.ph_unit_grid_size as i32;
+    for (k, v) in map {
         let (x, y) = if use_grid_pos {
-            let (x, y) = (
-                k.0 * PH_UNIT_GRID_SIZE as i32,
-                k.1 * PH_UNIT_GRID_SIZE as i32,
-            );
-            window_to_grid(x, y)
+            let (x, y) = (k.0 * unit, k.1 * unit);
+            window_to_grid(x, y, config)
         } else {
             (k.0, k.1)
         };
 
-        let idx = y * w as i32 + x;
-        let strength = cmp::min((*v as u32).saturating_mul(5), u8::MAX.into()) as u8;
+        // 世界没有边界时图外的格子不画，否则会卷到相邻的行上
+        if x < 0 || y < 0 || x >= w as i32 || y >= h as i32 {
+            continue;
+        }
+        let strength = cmp::min((v as u32).saturating_mul(5), u8::MAX.into()) as u8;
 
-        let idx = (idx as usize).saturating_mul(4);
-        if idx.saturating_add(3) >= img_bytes.len() || strength < PH_GRID_VIZ_MIN_STRENGTH {
+        let idx = (y as usize * w + x as usize) * 4;
+        if idx + 3 >= img_bytes.len() || strength < config.ph_grid_viz_min_strength {
             continue;
         }
 
-        img_bytes[idx + 3] = cmp::min(img_bytes[idx + 3].saturating_add(strength), PH_GRID_OPACITY);
-        img_bytes[idx] = color.0;
-        img_bytes[idx + 1] = color.1;
-        img_bytes[idx + 2] = color.2;
+        blend_pixel(&mut img_bytes[idx..idx + 4], color, strength, opacity);
+    }
+}
+
+/// 把一块（[`CHUNK_SIZE`]² 个值，按行排列，y 向上）画到这块自己的图上，图的第一行在上面
+pub fn add_chunk_to_img(
+    values: &[f32],
+    color: &(u8, u8, u8),
+    img_bytes: &mut [u8],
+    opacity: u8,
+    config: &SimConfig,
+) {
+    let size = CHUNK_SIZE as usize;
+    for (i, v) in values.iter().enumerate() {
+        let strength = cmp::min((*v as u32).saturating_mul(5), u8::MAX.into()) as u8;
+        if *v <= 0.0 || strength < config.ph_grid_viz_min_strength {
+            continue;
+        }
+        let (x, y) = (i % size, i / size);
+        let idx = ((size - 1 - y) * size + x) * 4;
+        blend_pixel(&mut img_bytes[idx..idx + 4], color, strength, opacity);
+    }
+}
+
+fn blend_pixel(pixel: &mut [u8], color: &(u8, u8, u8), strength: u8, opacity: u8) {
+    pixel[3] = cmp::min(pixel[3].saturating_add(strength), opacity);
+    pixel[0] = color.0;
+    pixel[1] = color.1;
+    pixel[2] = color.2;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decay::LinearDecay, GridBackend};
+
+    /// 一团跨过 0 的信号，有强有弱
+    fn blob() -> Vec<((i32, i32), f32)> {
+        let mut cells = Vec::new();
+        for x in -6..=6i32 {
+            for y in -4..=5 {
+                cells.push(((x, y), ((x * 7 + y * 13).rem_euclid(40) + 1) as f32));
+            }
+        }
+        cells
+    }
+
+    fn grid(
+        backend: GridBackend,
+        cells: impl IntoIterator<Item = ((i32, i32), f32)>,
+        max: f32,
+    ) -> DecayGrid {
+        let mut grid = DecayGrid::with_store(
+            backend.store((-50, -50), (50, 50)),
+            max,
+            Box::new(LinearDecay(0.0)),
+        );
+        for (key, value) in cells {
+            grid.set_value(&key, value);
+        }
+        grid
+    }
+
+    fn total(grid: &DecayGrid) -> f32 {
+        grid.cells().map(|(_, v)| v).sum()
+    }
+
+    fn sorted(grid: &DecayGrid) -> Vec<((i32, i32), f32)> {
+        let mut cells: Vec<_> = grid.cells().collect();
+        cells.sort_by_key(|(key, _)| *key);
+        cells
+    }
+
+    #[test]
+    fn diffusion_conserves_mass_without_loss() {
+        let mut grid = grid(GridBackend::Sparse, blob(), f32::MAX);
+        let before = total(&grid);
+        for _ in 0..5 {
+            grid.diffuse(0.3, 0.0, |_| false);
+        }
+        assert!(grid.len() > blob().len());
+        assert!((total(&grid) - before).abs() <= before * 1e-5);
+    }
+
+    #[test]
+    fn diffusion_keeps_blocked_share_in_place() {
+        // x = 2 是一堵墙
+        let wall = |key: &(i32, i32)| key.0 == 2;
+        let cells = blob().into_iter().filter(|(key, _)| !wall(key));
+        let mut grid = grid(GridBackend::Sparse, cells, f32::MAX);
+        let before = total(&grid);
+        for _ in 0..5 {
+            grid.diffuse(0.3, 0.0, wall);
+        }
+        assert!(grid.cells().all(|(key, _)| !wall(&key)));
+        assert!((total(&grid) - before).abs() <= before * 1e-5);
+
+        // 有损失时总量变少
+        grid.diffuse(0.3, 0.5, wall);
+        assert!(total(&grid) < before);
+    }
+
+    #[test]
+    fn diffusion_does_not_depend_on_iteration_order() {
+        let max = 30.0;
+        let mut grids = [
+            grid(GridBackend::Sparse, blob(), max),
+            grid(GridBackend::Sparse, blob().into_iter().rev(), max),
+            grid(GridBackend::Dense, blob(), max),
+            grid(GridBackend::Chunked, blob().into_iter().rev(), max),
+        ];
+        let wall = |key: &(i32, i32)| key.1 == -2 && key.0 > 0;
+        for grid in grids.iter_mut() {
+            for _ in 0..3 {
+                grid.diffuse(0.4, 0.1, wall);
+            }
+        }
+
+        let expected = sorted(&grids[0]);
+        assert!(expected.iter().any(|(_, v)| *v < max));
+        for grid in grids.iter().skip(1) {
+            assert_eq!(sorted(grid), expected);
+        }
     }
 }
@@ -0,0 +1,44 @@
+use bevy::prelude::{App, MinimalPlugins};
+
+use crate::{
+    ant::AntPlugin,
+    colony::ColonyPlugin,
+    control::{SimControl, SimControlPlugin},
+    food::FoodPlugin,
+    obstacle::ObstaclePlugin,
+    pheromone::PheromonePlugin,
+    snapshot::SnapshotPlugin,
+    SimConfig,
+};
+
+/// 无窗口的模拟：只有 [`AntPlugin`]、[`ColonyPlugin`]、[`FoodPlugin`] 和 [`PheromonePlugin`]，没有贴图和图层渲染，
+/// 每次 `update` 固定运行一步
+pub fn headless_app(config: SimConfig) -> App {
+    let mut app = App::new();
+    app.insert_resource(config)
+        .insert_resource(SimControl::fixed_ticks(1))
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SimControlPlugin)
+        .add_plugins(ObstaclePlugin)
+        .add_plugins(ColonyPlugin)
+        .add_plugins(FoodPlugin)
+        .add_plugins(PheromonePlugin)
+        .add_plugins(AntPlugin)
+        .add_plugins(SnapshotPlugin);
+    app
+}
+
+/// 以最快速度跑 `ticks` 步，返回 App 方便读取模拟结果
+pub fn run_headless(config: SimConfig, ticks: u64) -> App {
+    run_app(headless_app(config), ticks)
+}
+
+/// 对已经搭好的 App 跑 `ticks` 步，可以在之前先发送事件（比如读取快照）
+pub fn run_app(mut app: App, ticks: u64) -> App {
+    app.finish();
+    app.cleanup();
+    for _ in 0..ticks {
+        app.update();
+    }
+    app
+}
@@ -1,8 +1,26 @@
 pub mod ant;
+pub mod charts;
+pub mod colony;
 pub mod configs;
+pub mod control;
+pub mod decay;
+pub mod editor;
+pub mod food;
 pub mod grids;
+pub mod headless;
+pub mod map;
+pub mod metrics;
+pub mod obstacle;
+pub mod panel;
 pub mod pathviz;
 pub mod pheromone;
+pub mod recording;
+pub mod reload;
+pub mod replay;
+pub mod snapshot;
+pub mod store;
+pub mod sweep;
+pub mod tune;
 pub mod utils;
 
 pub use configs::*;
@@ -4,23 +4,142 @@ use bevy::{
     prelude::*,
 };
 
-use ants::{ant::AntPlugin, pathviz::PathVizPlugin, pheromone::PheromonePlugin, *};
+use std::time::Instant;
+
+use ants::{
+    ant::{Ant, AntDeaths, AntPlugin, AntRenderPlugin},
+    charts::ChartsPlugin,
+    colony::{Colony, ColonyId, ColonyPlugin, ColonyRenderPlugin},
+    editor::EditorPlugin,
+    food::{FoodPlugin, FoodRenderPlugin, FoodSource},
+    headless::{headless_app, run_app},
+    metrics::MetricsPlugin,
+    obstacle::{ObstaclePlugin, ObstacleRenderPlugin},
+    panel::ControlPanelPlugin,
+    pathviz::PathVizPlugin,
+    pheromone::{PheromonePlugin, PheromoneRenderPlugin, Pheromones},
+    recording::{RecorderPlugin, Recording},
+    reload::ConfigReloadPlugin,
+    replay::ReplayPlugin,
+    snapshot::{LoadSnapshot, Snapshot, SnapshotPlugin},
+    utils::SimRng,
+    *,
+};
 use bevy_pancam::{PanCam, PanCamPlugin};
 
 #[derive(Component)]
 struct FollowCamera;
 
+/// 无窗口模式默认运行的步数
+const DEFAULT_HEADLESS_TICKS: u64 = 10_000;
+
+#[derive(Default)]
+struct Args {
+    config_path: Option<String>,
+    headless: bool,
+    ticks: Option<u64>,
+    overrides: ConfigOverrides,
+    load_snapshot: Option<String>,
+    save_snapshot: Option<String>,
+    record: Option<String>,
+    record_every: Option<u64>,
+    replay: Option<String>,
+    metrics: Option<String>,
+    metrics_interval: Option<f32>,
+}
+
+impl Args {
+    fn metrics_plugin(&self) -> MetricsPlugin {
+        let mut metrics = MetricsPlugin::default();
+        if let Some(interval) = self.metrics_interval {
+            metrics = metrics.interval(interval);
+        }
+        if let Some(path) = &self.metrics {
+            metrics = metrics.export_to(path);
+        }
+        metrics
+    }
+}
+
 fn main() {
-    App::new()
+    let args = parse_args();
+    if let Some(path) = &args.replay {
+        run_replay(path);
+        return;
+    }
+    let config = match &args.config_path {
+        Some(path) => SimConfig::load(path),
+        None => Ok(SimConfig::default()),
+    };
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = args.overrides.apply(&mut config) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    if args.record.is_some() && config.world_unbounded {
+        eprintln!("--record needs a bounded world, positions are stored relative to w x h");
+        std::process::exit(1);
+    }
+
+    if args.headless {
+        run_headless_and_report(config, &args);
+        return;
+    }
+
+    let mut app = window_app(config);
+    app.add_plugins(PheromonePlugin)
+        .add_plugins(PheromoneRenderPlugin)
+        .add_plugins(PathVizPlugin)
+        .add_plugins(ObstaclePlugin)
+        .add_plugins(ObstacleRenderPlugin)
+        .add_plugins(ColonyPlugin)
+        .add_plugins(ColonyRenderPlugin)
+        .add_plugins(FoodPlugin)
+        .add_plugins(FoodRenderPlugin)
+        .add_plugins(AntPlugin)
+        .add_plugins(AntRenderPlugin)
+        .add_plugins(SnapshotPlugin)
+        .add_plugins(args.metrics_plugin())
+        .add_plugins(ChartsPlugin)
+        .add_plugins(ControlPanelPlugin)
+        .add_plugins(EditorPlugin);
+
+    if let Some(path) = args.load_snapshot {
+        app.world.send_event(LoadSnapshot(path.into()));
+    }
+    if let Some(path) = args.record {
+        app.add_plugins(RecorderPlugin::new(path).every(args.record_every.unwrap_or(1)));
+    }
+    if let Some(path) = args.config_path {
+        app.add_plugins(ConfigReloadPlugin::new(path).with_overrides(args.overrides));
+    }
+
+    app.run();
+}
+
+/// 窗口、镜头和背景，模拟和回放共用
+fn window_app(config: SimConfig) -> App {
+    let bg_color = config.bg_color;
+    let resolution = (config.w, config.h);
+
+    let mut app = App::new();
+    app.insert_resource(config)
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        /// TODO support resizable
+                        // TODO support resizable
                         resizable: false,
                         focused: true,
-                        resolution: (W, H).into(),
+                        resolution: resolution.into(),
                         title: "Ants".to_string(),
                         ..default()
                     }),
@@ -30,18 +149,180 @@ fn main() {
         .add_plugins(LogDiagnosticsPlugin::default())
         .add_plugins(FrameTimeDiagnosticsPlugin)
         .add_plugins(PanCamPlugin)
-        .add_plugins(PheromonePlugin)
-        .add_plugins(PathVizPlugin)
         .insert_resource(ClearColor(Color::rgba_u8(
-            BG_COLOR.0, BG_COLOR.1, BG_COLOR.2, 0,
+            bg_color.0, bg_color.1, bg_color.2, 0,
         )))
         .add_systems(Update, bevy::window::close_on_esc)
-        .add_systems(Startup, setup)
-        .add_plugins(AntPlugin)
-        .run();
+        .add_systems(Startup, setup);
+    app
+}
+
+/// 回放录像，只添加贴图和图层渲染，不运行模拟逻辑
+fn run_replay(path: &str) {
+    let recording = match Recording::load(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut app = window_app(recording.config.clone());
+    app.insert_resource(recording)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(PheromoneRenderPlugin)
+        .add_plugins(ObstacleRenderPlugin)
+        .add_plugins(ColonyRenderPlugin)
+        .add_plugins(FoodRenderPlugin)
+        .add_plugins(AntRenderPlugin);
+    app.run();
+}
+
+/// `--config <path>` 指定 RON 配置文件，不指定时使用默认参数，指定了文件时运行期间会监视它的改动
+/// `--headless [--ticks N]` 不开窗口，跑完 N 步后输出结果
+/// `--seed N` 指定随机数种子，覆盖配置文件里的值
+/// `--map <path>` 从 PNG 地图生成巢穴、食物和墙，覆盖配置文件里的值
+/// `--steering midpoint|antenna` 选择蚂蚁的转向方式，覆盖配置文件里的值
+/// `--load-snapshot <path>` 从快照继续运行，`--save-snapshot <path>` 在无窗口模式跑完后保存快照
+/// `--record <path> [--record-every N]` 每 N 步录一帧，`--replay <path>` 回放录像
+/// `--metrics <path> [--metrics-interval SECS]` 每隔 SECS 模拟秒导出一次统计，`.csv` 导出 CSV，其他是 JSON lines
+fn parse_args() -> Args {
+    let mut parsed = Args::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => parsed.config_path = args.next(),
+            "--headless" => parsed.headless = true,
+            "--ticks" => parsed.ticks = Some(parse_number(&arg, args.next())),
+            "--seed" => parsed.overrides.seed = Some(parse_number(&arg, args.next())),
+            "--map" => parsed.overrides.map = args.next(),
+            "--steering" => match args.next().map(|v| v.parse()) {
+                Some(Ok(steering)) => parsed.overrides.steering = Some(steering),
+                Some(Err(e)) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+                None => {
+                    eprintln!("--steering expects midpoint or antenna");
+                    std::process::exit(1);
+                }
+            },
+            "--load-snapshot" => parsed.load_snapshot = args.next(),
+            "--save-snapshot" => parsed.save_snapshot = args.next(),
+            "--record" => parsed.record = args.next(),
+            "--record-every" => parsed.record_every = Some(parse_number(&arg, args.next())),
+            "--replay" => parsed.replay = args.next(),
+            "--metrics" => parsed.metrics = args.next(),
+            "--metrics-interval" => {
+                parsed.metrics_interval = Some(parse_seconds(&arg, args.next()))
+            }
+            _ => {
+                eprintln!("unknown argument `{arg}`");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    parsed
+}
+
+fn parse_seconds(flag: &str, value: Option<String>) -> f32 {
+    match value.map(|v| v.parse::<f32>()) {
+        Some(Ok(secs)) if secs > 0.0 => secs,
+        _ => {
+            eprintln!("{flag} expects a positive number of seconds");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_number(flag: &str, value: Option<String>) -> u64 {
+    match value.map(|v| v.parse()) {
+        Some(Ok(n)) => n,
+        _ => {
+            eprintln!("{flag} expects a non-negative integer");
+            std::process::exit(1);
+        }
+    }
 }
 
-fn setup(mut commands: Commands, assert_server: Res<AssetServer>) {
+fn run_headless_and_report(config: SimConfig, args: &Args) {
+    let ticks = args.ticks.unwrap_or(DEFAULT_HEADLESS_TICKS);
+    let start = Instant::now();
+    let mut app = headless_app(config);
+    if let Some(path) = &args.load_snapshot {
+        // 读取失败时直接退出，而不是从头跑一遍
+        if let Err(e) = Snapshot::load(path) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        app.world.send_event(LoadSnapshot(path.into()));
+    }
+    if let Some(path) = &args.record {
+        app.add_plugins(RecorderPlugin::new(path).every(args.record_every.unwrap_or(1)));
+    }
+    if args.metrics.is_some() {
+        app.add_plugins(args.metrics_plugin());
+    }
+    let mut app = run_app(app, ticks);
+    if let Some(path) = &args.save_snapshot {
+        if let Err(e) = Snapshot::capture(&mut app.world).save(path) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    let ants = app
+        .world
+        .query_filtered::<(), With<Ant>>()
+        .iter(&app.world)
+        .count();
+    let food_left: f32 = app
+        .world
+        .query::<&FoodSource>()
+        .iter(&app.world)
+        .map(|food| food.amount)
+        .sum();
+    let seed = app.world.resource::<SimRng>().seed;
+    println!("{ticks} ticks (seed {seed}) in {:.2?}", start.elapsed());
+    let deaths = app.world.resource::<AntDeaths>();
+    println!(
+        "ants: {ants} ({} starved, {} died of old age), food left: {food_left}",
+        deaths.starved, deaths.old_age,
+    );
+
+    let ant_colonies = app
+        .world
+        .query_filtered::<&ColonyId, With<Ant>>()
+        .iter(&app.world)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut colonies = app
+        .world
+        .query::<&Colony>()
+        .iter(&app.world)
+        .cloned()
+        .collect::<Vec<_>>();
+    colonies.sort_by_key(|colony| colony.id.0);
+
+    let pheromones = app.world.resource::<Pheromones>();
+    for colony in colonies {
+        let ants = ant_colonies.iter().filter(|id| **id == colony.id).count();
+        let layers = &pheromones.colonies[colony.id.0];
+        println!(
+            "colony {} at {}: {ants} ants, stockpile {}, delivered {}, births {}",
+            colony.id.0, colony.pos, colony.stockpile, colony.delivered, colony.births,
+        );
+        println!(
+            "  delivery rate {:.2}/s, pheromone cells: to_food {}, to_home {}",
+            colony.delivery_rate,
+            layers.to_food.signal_count(),
+            layers.to_home.signal_count(),
+        );
+    }
+}
+
+fn setup(mut commands: Commands) {
     commands
         .spawn((
             Camera2dBundle {
@@ -56,26 +337,4 @@ fn setup(mut commands: Commands, assert_server: Res<AssetServer>) {
             BloomSettings::default(),
         ))
         .insert(PanCam::default());
-
-    commands.spawn(SpriteBundle {
-        texture: assert_server.load(SPRITE_ANT_COLONY),
-        sprite: Sprite {
-            color: Color::rgb(1.5, 1.5, 1.5),
-            ..default()
-        },
-        transform: Transform::from_xyz(HOME_LOCATION.0, HOME_LOCATION.1, 2.0)
-            .with_scale(Vec3::splat(HOME_SPRITE_SCALE)),
-        ..default()
-    });
-
-    commands.spawn(SpriteBundle {
-        texture: assert_server.load(SPRITE_FOOD),
-        sprite: Sprite {
-            color: Color::rgb(1.5, 1.5, 1.5),
-            ..default()
-        },
-        transform: Transform::from_xyz(FOOD_LOCATION.0, FOOD_LOCATION.1, 2.0)
-            .with_scale(Vec3::splat(FOOD_SPRITE_SCALE)),
-        ..default()
-    });
 }
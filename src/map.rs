@@ -0,0 +1,212 @@
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{ColonySpec, ConfigError, FoodSpec, ShapeSpec, SimConfig, TerrainSpec};
+
+/// 食物源的最小拾取半径，避免一两个像素的食物拾取不到
+const MIN_FOOD_RADIUS: f32 = 10.0;
+
+/// 地图像素的含义，颜色约定见 `maps/README.md`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MapPixel {
+    Open,
+    Wall,
+    Nest,
+    /// 绿色的亮度，决定食物量
+    Food(f32),
+    /// 灰色的亮度，决定地形上的速度
+    Terrain(f32),
+}
+
+impl MapPixel {
+    fn classify(&Rgba([r, g, b, a]): &Rgba<u8>) -> Self {
+        if a < 128 {
+            return MapPixel::Open;
+        }
+        if r < 64 && g < 64 && b < 64 {
+            return MapPixel::Wall;
+        }
+        if r >= 128 && g < 100 && b < 100 {
+            return MapPixel::Nest;
+        }
+        if g >= 64 && r < 100 && b < 100 {
+            return MapPixel::Food(g as f32 / 255.0);
+        }
+
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        if max - min < 24 {
+            // 按 0.1 取整，相邻的同速像素才能合并
+            let speed = ((r as f32 + g as f32 + b as f32) / 3.0 / 255.0 * 10.0).round() / 10.0;
+            if speed < 1.0 {
+                return MapPixel::Terrain(speed.max(0.1));
+            }
+        }
+        MapPixel::Open
+    }
+}
+
+/// 读取 PNG 地图，用图里的巢穴、食物、墙和地形替换配置里的对应部分；
+/// 图片拉伸到整个世界，巢穴按从上到下、从左到右的顺序对应 `colonies` 里的颜色
+pub fn apply_map(config: &mut SimConfig, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+    let img = image::open(path.as_ref())
+        .map_err(ConfigError::Map)?
+        .to_rgba8();
+    let map = WorldMap::new(&img, config);
+
+    let nests = map.blobs(|pixel| pixel == MapPixel::Nest);
+    if nests.is_empty() {
+        return Err(ConfigError::invalid("map", "has no nest (red) pixels"));
+    }
+    config.colonies = nests
+        .iter()
+        .enumerate()
+        .map(|(i, blob)| ColonySpec {
+            location: map.centroid(blob),
+            ..config.colonies.get(i).cloned().unwrap_or_default()
+        })
+        .collect();
+
+    config.food_sources = map
+        .blobs(|pixel| matches!(pixel, MapPixel::Food(_)))
+        .iter()
+        .map(|blob| {
+            let intensity: f32 = blob
+                .iter()
+                .map(|&(x, y)| match map.pixel(x, y) {
+                    MapPixel::Food(v) => v,
+                    _ => 0.0,
+                })
+                .sum();
+            let area = blob.len() as f32 * map.pixel_size.0 * map.pixel_size.1;
+            FoodSpec {
+                location: map.centroid(blob),
+                radius: (area / std::f32::consts::PI).sqrt().max(MIN_FOOD_RADIUS),
+                amount: intensity * config.map_food_per_pixel,
+            }
+        })
+        .collect();
+
+    config.obstacles = map
+        .row_runs(|pixel| pixel == MapPixel::Wall)
+        .into_iter()
+        .map(|(shape, _)| shape)
+        .collect();
+    config.terrain = map
+        .row_runs(|pixel| matches!(pixel, MapPixel::Terrain(_)))
+        .into_iter()
+        .map(|(shape, pixel)| TerrainSpec {
+            shape,
+            speed: match pixel {
+                MapPixel::Terrain(speed) => speed,
+                _ => 1.0,
+            },
+        })
+        .collect();
+
+    Ok(())
+}
+
+struct WorldMap {
+    pixels: Vec<MapPixel>,
+    size: (usize, usize),
+    /// 一个像素在世界里的宽高
+    pixel_size: (f32, f32),
+    half_world: (f32, f32),
+}
+
+impl WorldMap {
+    fn new(img: &RgbaImage, config: &SimConfig) -> Self {
+        let size = (img.width() as usize, img.height() as usize);
+        Self {
+            pixels: img.pixels().map(MapPixel::classify).collect(),
+            size,
+            pixel_size: (config.w / size.0 as f32, config.h / size.1 as f32),
+            half_world: (config.w / 2.0, config.h / 2.0),
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> MapPixel {
+        self.pixels[y * self.size.0 + x]
+    }
+
+    /// 像素左上角的世界坐标（y 轴向上）
+    fn to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.pixel_size.0 - self.half_world.0,
+            self.half_world.1 - y * self.pixel_size.1,
+        )
+    }
+
+    fn centroid(&self, blob: &[(usize, usize)]) -> (f32, f32) {
+        let n = blob.len() as f32;
+        let (sx, sy) = blob.iter().fold((0.0, 0.0), |(sx, sy), &(x, y)| {
+            (sx + x as f32, sy + y as f32)
+        });
+        self.to_world(sx / n + 0.5, sy / n + 0.5)
+    }
+
+    /// 四连通的像素块，按扫描顺序返回
+    fn blobs(&self, is_match: impl Fn(MapPixel) -> bool) -> Vec<Vec<(usize, usize)>> {
+        let (w, h) = self.size;
+        let mut visited = vec![false; w * h];
+        let mut blobs = Vec::new();
+
+        for start in 0..w * h {
+            if visited[start] || !is_match(self.pixels[start]) {
+                continue;
+            }
+
+            let mut blob = Vec::new();
+            let mut stack = vec![start];
+            visited[start] = true;
+            while let Some(idx) = stack.pop() {
+                let (x, y) = (idx % w, idx / w);
+                blob.push((x, y));
+
+                let neighbours = [
+                    (x > 0).then(|| idx - 1),
+                    (x + 1 < w).then(|| idx + 1),
+                    (y > 0).then(|| idx - w),
+                    (y + 1 < h).then(|| idx + w),
+                ];
+                for next in neighbours.into_iter().flatten() {
+                    if !visited[next] && is_match(self.pixels[next]) {
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+            blobs.push(blob);
+        }
+
+        blobs
+    }
+
+    /// 每一行里连续且相同的像素合并成一个矩形，减少形状数量
+    fn row_runs(&self, is_match: impl Fn(MapPixel) -> bool) -> Vec<(ShapeSpec, MapPixel)> {
+        let (w, h) = self.size;
+        let mut runs = Vec::new();
+
+        for y in 0..h {
+            let mut x = 0;
+            while x < w {
+                let pixel = self.pixel(x, y);
+                if !is_match(pixel) {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                while x < w && self.pixel(x, y) == pixel {
+                    x += 1;
+                }
+                let min = self.to_world(start as f32, y as f32 + 1.0);
+                let max = self.to_world(x as f32, y as f32);
+                runs.push((ShapeSpec::Rect { min, max }, pixel));
+            }
+        }
+
+        runs
+    }
+}
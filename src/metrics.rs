@@ -0,0 +1,356 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    log::warn,
+    prelude::{EventReader, IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource, Update, With},
+};
+use serde::Serialize;
+
+use crate::{
+    ant::{Ant, AntTask, CurrentTask, FoodDelivered, FoodPickedUp},
+    control::{SimClock, SimControlPlugin, SimReset, SimSet, SimUpdate},
+    pheromone::Pheromones,
+    ResetSimulation,
+};
+
+/// 内存里最多保留的统计条数
+const MAX_HISTORY: usize = 3600;
+
+/// 导出格式，按文件扩展名选择：`.csv` 是 CSV，其他都是 JSON lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    JsonLines,
+}
+
+impl MetricsFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => MetricsFormat::Csv,
+            _ => MetricsFormat::JsonLines,
+        }
+    }
+}
+
+/// 觅食统计：每隔 interval 模拟秒汇总一次拾取、送达、往返用时、各任务的蚂蚁数和信息素图层的情况，
+/// 保存在 [`MetricsHistory`] 里，指定了文件时同时导出
+pub struct MetricsPlugin {
+    interval: f32,
+    export: Option<PathBuf>,
+}
+
+impl Default for MetricsPlugin {
+    fn default() -> Self {
+        Self {
+            interval: 1.0,
+            export: None,
+        }
+    }
+}
+
+impl MetricsPlugin {
+    /// 统计间隔（模拟秒）
+    pub fn interval(mut self, secs: f32) -> Self {
+        self.interval = secs;
+        self
+    }
+
+    pub fn export_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.export = Some(path.into());
+        self
+    }
+}
+
+/// 一个信息素图层的统计
+#[derive(Debug, Clone, Serialize)]
+pub struct GridMetrics {
+    pub colony: usize,
+    pub layer: &'static str,
+    /// 有信号的格子数
+    pub cells: usize,
+    pub total_strength: f32,
+    /// 区域查询索引的大小，见 [`crate::grids::WorldGrid::index_size`]
+    pub index_size: usize,
+    /// 这一段时间内转向缓存的命中率，没有查询时为 None
+    pub steer_cache_hit_rate: Option<f32>,
+}
+
+/// 一段时间的统计，计数都是这一段时间内的，蚂蚁数和图层是段末的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSample {
+    pub tick: u64,
+    pub time: f64,
+    /// 这一段的长度（模拟秒）
+    pub interval: f32,
+    pub pickups: u32,
+    pub deliveries: u32,
+    /// 送达的蚂蚁从出门到送达的平均秒数，没有送达时为 None
+    pub avg_round_trip: Option<f32>,
+    pub ants_find_food: u32,
+    pub ants_find_home: u32,
+    /// 所有图层合计的转向缓存命中率
+    pub steer_cache_hit_rate: Option<f32>,
+    pub grids: Vec<GridMetrics>,
+}
+
+/// 最近的统计，最旧的在前
+#[derive(Resource, Debug, Default)]
+pub struct MetricsHistory {
+    pub samples: VecDeque<MetricsSample>,
+    /// 第一次送达食物的模拟时间
+    pub first_delivery: Option<f64>,
+}
+
+#[derive(Resource, Debug)]
+struct MetricsCollector {
+    interval: f32,
+    started: f64,
+    /// 上一步的步数，不连续（重置、读取快照）时从这一步重新开始计时
+    last_tick: u64,
+    pickups: u32,
+    deliveries: u32,
+    trip_time: f32,
+}
+
+#[derive(Resource)]
+struct MetricsExport {
+    path: PathBuf,
+    format: MetricsFormat,
+    writer: Option<BufWriter<File>>,
+    failed: bool,
+}
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+
+        app.insert_resource(MetricsCollector {
+            interval: self.interval,
+            started: 0.0,
+            last_tick: 0,
+            pickups: 0,
+            deliveries: 0,
+            trip_time: 0.0,
+        })
+        .init_resource::<MetricsHistory>()
+        .add_event::<ResetSimulation>()
+        .add_event::<FoodPickedUp>()
+        .add_event::<FoodDelivered>()
+        .add_systems(Update, reset_metrics.in_set(SimReset))
+        .add_systems(
+            SimUpdate,
+            (
+                count_foraging.after(SimSet::Ants),
+                sample_metrics.after(SimSet::Pheromones),
+            )
+                .chain(),
+        );
+
+        if let Some(path) = &self.export {
+            app.insert_resource(MetricsExport {
+                path: path.clone(),
+                format: MetricsFormat::from_path(path),
+                writer: None,
+                failed: false,
+            });
+        }
+    }
+}
+
+fn reset_metrics(
+    mut reset_events: EventReader<ResetSimulation>,
+    mut collector: ResMut<MetricsCollector>,
+    mut history: ResMut<MetricsHistory>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    collector.started = 0.0;
+    collector.pickups = 0;
+    collector.deliveries = 0;
+    collector.trip_time = 0.0;
+    history.samples.clear();
+    history.first_delivery = None;
+}
+
+fn count_foraging(
+    mut collector: ResMut<MetricsCollector>,
+    mut history: ResMut<MetricsHistory>,
+    mut pickups: EventReader<FoodPickedUp>,
+    mut deliveries: EventReader<FoodDelivered>,
+    clock: Res<SimClock>,
+) {
+    collector.pickups += pickups.iter().count() as u32;
+    for delivery in deliveries.iter() {
+        collector.deliveries += 1;
+        collector.trip_time += delivery.trip_time;
+        history.first_delivery.get_or_insert(clock.elapsed);
+    }
+}
+
+fn hit_rate((hits, misses): (u32, u32)) -> Option<f32> {
+    let total = hits + misses;
+    (total > 0).then(|| hits as f32 / total as f32)
+}
+
+fn sample_metrics(
+    mut collector: ResMut<MetricsCollector>,
+    mut history: ResMut<MetricsHistory>,
+    export: Option<ResMut<MetricsExport>>,
+    clock: Res<SimClock>,
+    ant_query: Query<&CurrentTask, With<Ant>>,
+    mut pheromones: ResMut<Pheromones>,
+) {
+    if clock.tick != collector.last_tick + 1 {
+        collector.started = clock.previous;
+    }
+    collector.last_tick = clock.tick;
+    let interval = (clock.elapsed - collector.started) as f32;
+    if interval < collector.interval {
+        return;
+    }
+
+    let (mut ants_find_food, mut ants_find_home) = (0, 0);
+    for task in ant_query.iter() {
+        match task.0 {
+            AntTask::FindFood => ants_find_food += 1,
+            AntTask::FindHome => ants_find_home += 1,
+        }
+    }
+
+    let mut grids = Vec::new();
+    let (mut hits, mut misses) = (0, 0);
+    for (colony, layers) in pheromones.colonies.iter_mut().enumerate() {
+        for (layer, grid) in [
+            ("to_home", &mut layers.to_home),
+            ("to_food", &mut layers.to_food),
+        ] {
+            let stats = grid.take_steer_stats();
+            hits += stats.0;
+            misses += stats.1;
+            grids.push(GridMetrics {
+                colony,
+                layer,
+                cells: grid.signal_count(),
+                total_strength: grid.total_strength(),
+                index_size: grid.index_size(),
+                steer_cache_hit_rate: hit_rate(stats),
+            });
+        }
+    }
+
+    let sample = MetricsSample {
+        tick: clock.tick,
+        time: clock.elapsed,
+        interval,
+        pickups: collector.pickups,
+        deliveries: collector.deliveries,
+        avg_round_trip: (collector.deliveries > 0)
+            .then(|| collector.trip_time / collector.deliveries as f32),
+        ants_find_food,
+        ants_find_home,
+        steer_cache_hit_rate: hit_rate((hits, misses)),
+        grids,
+    };
+    collector.started = clock.elapsed;
+    collector.pickups = 0;
+    collector.deliveries = 0;
+    collector.trip_time = 0.0;
+
+    if let Some(mut export) = export.filter(|export| !export.failed) {
+        if let Err(e) = export.write(&sample) {
+            warn!("metrics export to {} stopped: {e}", export.path.display());
+            export.failed = true;
+        }
+    }
+    if history.samples.len() >= MAX_HISTORY {
+        history.samples.pop_front();
+    }
+    history.samples.push_back(sample);
+}
+
+impl MetricsExport {
+    fn write(&mut self, sample: &MetricsSample) -> io::Result<()> {
+        if self.writer.is_none() {
+            let mut writer = BufWriter::new(File::create(&self.path)?);
+            if self.format == MetricsFormat::Csv {
+                writeln!(writer, "{}", csv_header(sample))?;
+            }
+            self.writer = Some(writer);
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+
+        match self.format {
+            MetricsFormat::Csv => writeln!(writer, "{}", csv_row(sample))?,
+            MetricsFormat::JsonLines => {
+                serde_json::to_writer(&mut *writer, sample)?;
+                writeln!(writer)?;
+            }
+        }
+        // 每条都 flush，运行中也能直接读文件
+        writer.flush()
+    }
+}
+
+/// 每个图层占四列，列名按第一条统计里的图层生成，运行中蚁群数量变了列就对不上了
+fn csv_header(sample: &MetricsSample) -> String {
+    let mut columns: Vec<String> = [
+        "tick",
+        "time",
+        "interval",
+        "pickups",
+        "deliveries",
+        "avg_round_trip",
+        "ants_find_food",
+        "ants_find_home",
+        "steer_cache_hit_rate",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect();
+    for grid in sample.grids.iter() {
+        let prefix = format!("colony{}_{}", grid.colony, grid.layer);
+        for column in [
+            "cells",
+            "total_strength",
+            "index_size",
+            "steer_cache_hit_rate",
+        ] {
+            columns.push(format!("{prefix}_{column}"));
+        }
+    }
+    columns.join(",")
+}
+
+fn csv_row(sample: &MetricsSample) -> String {
+    let optional = |v: Option<f32>| v.map(|v| v.to_string()).unwrap_or_default();
+    let mut values = vec![
+        sample.tick.to_string(),
+        sample.time.to_string(),
+        sample.interval.to_string(),
+        sample.pickups.to_string(),
+        sample.deliveries.to_string(),
+        optional(sample.avg_round_trip),
+        sample.ants_find_food.to_string(),
+        sample.ants_find_home.to_string(),
+        optional(sample.steer_cache_hit_rate),
+    ];
+    for grid in sample.grids.iter() {
+        values.push(grid.cells.to_string());
+        values.push(grid.total_strength.to_string());
+        values.push(grid.index_size.to_string());
+        values.push(optional(grid.steer_cache_hit_rate));
+    }
+    values.join(",")
+}
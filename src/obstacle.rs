@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    math::vec2,
+    prelude::{
+        resource_changed, Assets, Commands, Component, EventReader, Handle, Image,
+        IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource, Startup, Transform, Update, Vec2,
+        Vec3, With,
+    },
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    sprite::SpriteBundle,
+};
+
+use crate::{
+    control::{SimControlPlugin, SimReset},
+    grids::add_map_to_grid_img,
+    ResetSimulation, ShapeSpec, SimConfig,
+};
+
+/// [`Obstacles::blocks_near`] 用的粗网格每格的边长（格子数）
+const BLOCKED_BUCKET_SIZE: i32 = 8;
+
+fn blocked_bucket(key: &(i32, i32)) -> (i32, i32) {
+    (
+        key.0.div_euclid(BLOCKED_BUCKET_SIZE),
+        key.1.div_euclid(BLOCKED_BUCKET_SIZE),
+    )
+}
+
+/// 障碍物图层：和信息素网格对齐的阻挡格子，世界边界之外也算阻挡；
+/// 同时记录难走地形格子上的速度系数
+#[derive(Resource, Debug, Clone)]
+pub struct Obstacles {
+    blocked: HashSet<(i32, i32)>,
+    /// 每个粗网格里阻挡格子的个数
+    blocked_buckets: HashMap<(i32, i32), usize>,
+    terrain: HashMap<(i32, i32), f32>,
+    unit_grid_size: i32,
+    half_size: (f32, f32),
+    /// 世界没有边界时 half_size 不限制任何东西
+    unbounded: bool,
+}
+
+impl Obstacles {
+    /// 把配置里的形状栅格化成格子，以格子在网格里的代表点判断
+    pub fn new(config: &SimConfig) -> Self {
+        let mut obstacles = Self {
+            blocked: HashSet::new(),
+            blocked_buckets: HashMap::new(),
+            terrain: HashMap::new(),
+            unit_grid_size: config.ph_unit_grid_size as i32,
+            half_size: (config.w / 2.0, config.h / 2.0),
+            unbounded: config.world_unbounded,
+        };
+
+        for terrain in config.terrain.iter() {
+            for key in obstacles.rasterize(&terrain.shape) {
+                obstacles.terrain.insert(key, terrain.speed);
+            }
+        }
+        for shape in config.obstacles.iter() {
+            for key in obstacles.rasterize(shape) {
+                obstacles.block(key);
+            }
+        }
+
+        obstacles
+    }
+
+    /// 直接用格子恢复（读取快照时用）
+    pub fn from_cells(
+        config: &SimConfig,
+        blocked: impl IntoIterator<Item = (i32, i32)>,
+        terrain: impl IntoIterator<Item = ((i32, i32), f32)>,
+    ) -> Self {
+        let mut obstacles = Self {
+            blocked: HashSet::new(),
+            blocked_buckets: HashMap::new(),
+            terrain: terrain.into_iter().collect(),
+            unit_grid_size: config.ph_unit_grid_size as i32,
+            half_size: (config.w / 2.0, config.h / 2.0),
+            unbounded: config.world_unbounded,
+        };
+        for key in blocked {
+            obstacles.block(key);
+        }
+        obstacles
+    }
+
+    /// 形状覆盖的、在世界范围内的格子
+    fn rasterize(&self, shape: &ShapeSpec) -> Vec<(i32, i32)> {
+        let unit = self.unit_grid_size;
+        let (max_x, max_y) = if self.unbounded {
+            (i32::MAX, i32::MAX)
+        } else {
+            (
+                self.half_size.0 as i32 / unit,
+                self.half_size.1 as i32 / unit,
+            )
+        };
+        let (min, max) = shape.bounds();
+        let x_range = (min.0 as i32 / unit - 1).max(-max_x)..=(max.0 as i32 / unit + 1).min(max_x);
+        let y_range = (min.1 as i32 / unit - 1).max(-max_y)..=(max.1 as i32 / unit + 1).min(max_y);
+
+        let mut cells = Vec::new();
+        for x in x_range {
+            for y in y_range.clone() {
+                if shape.contains(((x * unit) as f32, (y * unit) as f32)) {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    /// 世界坐标所在的格子，和 [`crate::grids::WorldGrid`] 的信息素格子一致
+    pub fn cell_key(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.unit_grid_size as f32).floor() as i32,
+            (pos.y / self.unit_grid_size as f32).floor() as i32,
+        )
+    }
+
+    /// 以 center 为圆心、radius 为半径覆盖的格子（编辑器画笔用）
+    pub fn cells_in_radius(&self, center: Vec2, radius: f32) -> Vec<(i32, i32)> {
+        let unit = self.unit_grid_size;
+        let (cx, cy) = self.cell_key(center);
+        let r = (radius / unit as f32).ceil() as i32;
+
+        let mut cells = Vec::new();
+        for x in cx - r..=cx + r {
+            for y in cy - r..=cy + r {
+                let pos = Vec2::new((x * unit) as f32, (y * unit) as f32);
+                if pos.distance_squared(center) <= radius * radius {
+                    cells.push((x, y));
+                }
+            }
+        }
+        if cells.is_empty() {
+            cells.push((cx, cy));
+        }
+        cells
+    }
+
+    /// 所在格子地形的速度系数，平地是 1
+    pub fn speed_factor(&self, pos: Vec2) -> f32 {
+        self.terrain
+            .get(&self.cell_key(pos))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    pub fn is_blocked_cell(&self, key: &(i32, i32)) -> bool {
+        self.blocked.contains(key)
+    }
+
+    /// 格子被墙挡住或在世界之外
+    pub fn blocks_cell(&self, key: &(i32, i32)) -> bool {
+        let unit = self.unit_grid_size as f32;
+        self.is_blocked_cell(key) || !self.in_world(vec2(key.0 as f32 * unit, key.1 as f32 * unit))
+    }
+
+    /// key 周围 radius 个格子以内可能有被挡住的格子；返回 false 时这个范围里的视线都不用检查。
+    /// 按粗网格判断，只会多报不会漏报
+    pub fn blocks_near(&self, key: (i32, i32), radius: f32) -> bool {
+        if self.blocked.is_empty() {
+            return false;
+        }
+        // 多算一圈，起点不一定在格子的代表点上
+        let r = radius.max(0.0).ceil() as i32 + 1;
+        let (min, max) = ((key.0 - r, key.1 - r), (key.0 + r, key.1 + r));
+        let unit = self.unit_grid_size as f32;
+        let inside = |(x, y): (i32, i32)| self.in_world(vec2(x as f32 * unit, y as f32 * unit));
+        if !inside(min) || !inside(max) {
+            return true;
+        }
+        let (min, max) = (blocked_bucket(&min), blocked_bucket(&max));
+        (min.0..=max.0).any(|x| (min.1..=max.1).any(|y| self.blocked_buckets.contains_key(&(x, y))))
+    }
+
+    pub fn in_world(&self, pos: Vec2) -> bool {
+        self.unbounded || (pos.x.abs() < self.half_size.0 && pos.y.abs() < self.half_size.1)
+    }
+
+    pub fn is_blocked(&self, pos: Vec2) -> bool {
+        !self.in_world(pos) || self.is_blocked_cell(&self.cell_key(pos))
+    }
+
+    /// 两点之间的连线是否没有穿过障碍物，按半个格子的步长检查
+    pub fn is_visible(&self, from: Vec2, to: Vec2) -> bool {
+        if self.blocked.is_empty() {
+            return true;
+        }
+
+        let step = self.unit_grid_size as f32 / 2.0;
+        let steps = (from.distance(to) / step).ceil() as usize;
+        (1..=steps).all(|i| !self.is_blocked(from.lerp(to, i as f32 / steps as f32)))
+    }
+
+    pub fn block(&mut self, key: (i32, i32)) {
+        if self.blocked.insert(key) {
+            *self
+                .blocked_buckets
+                .entry(blocked_bucket(&key))
+                .or_default() += 1;
+        }
+    }
+
+    pub fn unblock(&mut self, key: &(i32, i32)) {
+        if !self.blocked.remove(key) {
+            return;
+        }
+        let bucket = blocked_bucket(key);
+        if let Some(count) = self.blocked_buckets.get_mut(&bucket) {
+            *count -= 1;
+            if *count == 0 {
+                self.blocked_buckets.remove(&bucket);
+            }
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &(i32, i32)> {
+        self.blocked.iter()
+    }
+
+    pub fn terrain_cells(&self) -> impl Iterator<Item = (&(i32, i32), &f32)> {
+        self.terrain.iter()
+    }
+}
+
+pub struct ObstaclePlugin;
+
+/// 障碍物图层渲染，无窗口模式下不添加
+pub struct ObstacleRenderPlugin;
+
+#[derive(Component)]
+struct ObstacleImageRender;
+
+impl Plugin for ObstaclePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+        let config = app.world.resource::<SimConfig>().clone();
+
+        app.insert_resource(Obstacles::new(&config))
+            .add_event::<ResetSimulation>()
+            .add_systems(Update, reset_obstacles.in_set(SimReset));
+    }
+}
+
+impl Plugin for ObstacleRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Startup, setup).add_systems(
+            Update,
+            obstacle_image_update.run_if(resource_changed::<Obstacles>()),
+        );
+    }
+}
+
+fn reset_obstacles(
+    mut reset_events: EventReader<ResetSimulation>,
+    mut obstacles: ResMut<Obstacles>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    *obstacles = Obstacles::new(&config);
+}
+
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 1.5)
+                .with_scale(Vec3::splat(config.ph_unit_grid_size as f32)),
+            ..Default::default()
+        },
+        ObstacleImageRender,
+    ));
+}
+
+fn obstacle_image_update(
+    mut textures: ResMut<Assets<Image>>,
+    obstacles: Res<Obstacles>,
+    mut render_query: Query<(&mut Handle<Image>, &mut Transform), With<ObstacleImageRender>>,
+    config: Res<SimConfig>,
+) {
+    let Ok((mut img_handle, mut transform)) = render_query.get_single_mut() else {
+        return;
+    };
+    let (w, h) = config.grid_img_size();
+    let mut bytes = vec![0; w * h * 4];
+
+    // 越难走颜色越深
+    let terrain = obstacles
+        .terrain_cells()
+        .map(|(k, speed)| (*k, (1.0 - speed).max(0.0) * 51.0));
+    add_map_to_grid_img(
+        terrain,
+        &config.terrain_color,
+        &mut bytes,
+        true,
+        u8::MAX,
+        &config,
+    );
+
+    let cells = obstacles.cells().map(|k| (*k, f32::MAX));
+    add_map_to_grid_img(
+        cells,
+        &config.obstacle_color,
+        &mut bytes,
+        true,
+        u8::MAX,
+        &config,
+    );
+
+    let obstacle_map = Image::new(
+        Extent3d {
+            width: w as u32,
+            height: h as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        bytes,
+        TextureFormat::Rgba8Unorm,
+    );
+    *img_handle = textures.add(obstacle_map);
+    transform.scale = Vec3::splat(config.ph_unit_grid_size as f32);
+}
@@ -0,0 +1,312 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{
+    DetectChangesMut, EventWriter, Input, KeyCode, Plugin, Res, ResMut, Resource, Update,
+};
+use bevy_egui::{
+    egui::{self, Slider},
+    EguiContexts, EguiPlugin,
+};
+
+use crate::{
+    control::{SimControl, SimControlPlugin},
+    snapshot::{LoadSnapshot, SaveSnapshot, SnapshotPlugin},
+    DecaySpec, ResetSimulation, SimConfig, SteeringModel, MIN_STEERING_FORCE_FACTOR,
+};
+
+/// 左侧参数面板，拖动滑条直接修改 [`SimConfig`]
+pub struct ControlPanelPlugin;
+
+/// 面板上填写的快照文件路径，F5 保存、F9 读取也用它
+#[derive(Resource, Debug, Clone)]
+pub struct SnapshotPath(pub String);
+
+impl Default for SnapshotPath {
+    fn default() -> Self {
+        Self("snapshot.ron".to_string())
+    }
+}
+
+impl Plugin for ControlPanelPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+        if !app.is_plugin_added::<SnapshotPlugin>() {
+            app.add_plugins(SnapshotPlugin);
+        }
+
+        app.init_resource::<SimConfig>()
+            .init_resource::<SnapshotPath>()
+            .add_systems(Update, (snapshot_hotkeys, control_panel));
+    }
+}
+
+fn control_panel(
+    mut contexts: EguiContexts,
+    mut config: ResMut<SimConfig>,
+    mut control: ResMut<SimControl>,
+    mut reset_events: EventWriter<ResetSimulation>,
+    mut snapshot_path: ResMut<SnapshotPath>,
+    mut save_events: EventWriter<SaveSnapshot>,
+    mut load_events: EventWriter<LoadSnapshot>,
+) {
+    // 面板每帧都要拿 &mut，不绕过变更检测的话配置每帧都算改过，衰减模型等会跟着反复重建
+    let mut changed = false;
+    let config_ref = config.bypass_change_detection();
+    egui::SidePanel::left("control_panel").show(contexts.ctx_mut(), |ui| {
+        ui.heading("Simulation");
+        ui.horizontal(|ui| {
+            let label = if control.paused { "Resume" } else { "Pause" };
+            if ui.button(label).clicked() {
+                control.paused = !control.paused;
+            }
+            if ui
+                .add_enabled(control.paused, egui::Button::new("Step"))
+                .clicked()
+            {
+                control.step = true;
+            }
+            if ui.button("Reset").clicked() {
+                reset_events.send(ResetSimulation);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut snapshot_path.0);
+            if ui.button("Save (F5)").clicked() {
+                save_events.send(SaveSnapshot(PathBuf::from(&snapshot_path.0)));
+            }
+            if ui.button("Load (F9)").clicked() {
+                load_events.send(LoadSnapshot(PathBuf::from(&snapshot_path.0)));
+            }
+        });
+        ui.add(
+            Slider::new(&mut control.speed, 0.1..=5.0)
+                .logarithmic(true)
+                .text("speed"),
+        );
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.tick_hz, 10.0..=240.0)
+                    .logarithmic(true)
+                    .text("ticks per second"),
+            )
+            .changed();
+
+        ui.separator();
+        ui.heading("Ants");
+        changed |= ui
+            .add(Slider::new(&mut config_ref.ant_speed, 0.0..=300.0).text("speed"))
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_direction_randomness_deg, 0.0..=720.0)
+                    .text("direction randomness (deg)"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(
+                    &mut config_ref.ant_steering_force_factor,
+                    MIN_STEERING_FORCE_FACTOR..=2.0,
+                )
+                .text("steering force"),
+            )
+            .changed();
+        ui.horizontal(|ui| {
+            ui.label("steering");
+            changed |= ui
+                .radio_value(
+                    &mut config_ref.ant_steering,
+                    SteeringModel::Midpoint,
+                    "midpoint",
+                )
+                .changed();
+            changed |= ui
+                .radio_value(
+                    &mut config_ref.ant_steering,
+                    SteeringModel::Antenna,
+                    "antenna",
+                )
+                .changed();
+        });
+        if config_ref.ant_steering == SteeringModel::Antenna {
+            changed |= ui
+                .add(
+                    Slider::new(&mut config_ref.ant_antenna_angle_deg, 5.0..=90.0)
+                        .text("antenna angle (deg)"),
+                )
+                .changed();
+            changed |= ui
+                .add(
+                    Slider::new(&mut config_ref.ant_antenna_distance, 5.0..=200.0)
+                        .text("antenna distance"),
+                )
+                .changed();
+        }
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_ph_drop_interval, 0.05..=5.0)
+                    .logarithmic(true)
+                    .text("pheromone drop interval"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_ph_strength_decay_rate, 0.0..=10.0)
+                    .text("pheromone strength decay"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_ph_scan_radius_increment, 0.0..=2.0)
+                    .text("scan radius increment"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_ph_scan_radius_scale, 1.0..=5.0)
+                    .text("scan radius scale"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_energy_drain_rate, 0.0..=10.0).text("energy drain"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ant_lifespan, 10.0..=3600.0)
+                    .logarithmic(true)
+                    .text("lifespan (s)"),
+            )
+            .changed();
+
+        ui.separator();
+        ui.heading("Pheromones");
+        changed |= decay_model_ui(
+            ui,
+            "decay model",
+            &mut config_ref.ph_decay_model,
+            &mut config_ref.ph_decay_rate,
+        );
+        changed |= ui
+            .add(
+                Slider::new(
+                    &mut config_ref.ph_decay_rate,
+                    0.0..=max_decay_rate(&config_ref.ph_decay_model),
+                )
+                .logarithmic(true)
+                .text("decay rate"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                Slider::new(&mut config_ref.ph_decay_interval, 0.01..=2.0)
+                    .logarithmic(true)
+                    .text("decay interval"),
+            )
+            .changed();
+        changed |= ui
+            .add(Slider::new(&mut config_ref.ph_diffusion_rate, 0.0..=1.0).text("diffusion rate"))
+            .changed();
+        changed |= ui
+            .add(Slider::new(&mut config_ref.ph_diffusion_loss, 0.0..=1.0).text("diffusion loss"))
+            .changed();
+
+        ui.separator();
+        ui.heading("Visualization");
+        changed |= ui
+            .add(Slider::new(&mut config_ref.ph_grid_opacity, 0..=255).text("pheromone opacity"))
+            .changed();
+        changed |= ui
+            .add(Slider::new(&mut config_ref.viz_grid_opacity, 0..=255).text("path opacity"))
+            .changed();
+        changed |= decay_model_ui(
+            ui,
+            "path decay model",
+            &mut config_ref.viz_decay_model,
+            &mut config_ref.viz_decay_rate,
+        );
+        changed |= ui
+            .add(
+                Slider::new(
+                    &mut config_ref.viz_decay_rate,
+                    0.0..=max_decay_rate(&config_ref.viz_decay_model),
+                )
+                .text("path decay"),
+            )
+            .changed();
+    });
+    if changed {
+        config.set_changed();
+    }
+}
+
+/// 衰减速率滑条的上限，指数衰减的比例不能超过 1（见 [`SimConfig::validate`]）
+fn max_decay_rate(spec: &DecaySpec) -> f32 {
+    match spec {
+        DecaySpec::Exponential => 1.0,
+        _ => 2.0,
+    }
+}
+
+/// 选择衰减模型，半衰期和保持时间模型带一个秒数；换到指数衰减时把 rate 压到合法范围，
+/// 返回是否改动了
+fn decay_model_ui(ui: &mut egui::Ui, label: &str, spec: &mut DecaySpec, rate: &mut f32) -> bool {
+    let mut changed = false;
+    let name = |spec: &DecaySpec| match spec {
+        DecaySpec::Linear => "linear",
+        DecaySpec::Exponential => "exponential",
+        DecaySpec::HalfLife(_) => "half-life",
+        DecaySpec::TimeSinceDeposit(_) => "time since deposit",
+    };
+    egui::ComboBox::from_label(label)
+        .selected_text(name(spec))
+        .show_ui(ui, |ui| {
+            for option in [
+                DecaySpec::Linear,
+                DecaySpec::Exponential,
+                DecaySpec::HalfLife(5.0),
+                DecaySpec::TimeSinceDeposit(10.0),
+            ] {
+                let selected = std::mem::discriminant(spec) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, name(&option)).clicked() && !selected {
+                    *spec = option;
+                    *rate = rate.min(max_decay_rate(spec));
+                    changed = true;
+                }
+            }
+        });
+    if let DecaySpec::HalfLife(secs) | DecaySpec::TimeSinceDeposit(secs) = spec {
+        changed |= ui
+            .add(
+                Slider::new(secs, 0.1..=120.0)
+                    .logarithmic(true)
+                    .text("seconds"),
+            )
+            .changed();
+    }
+    changed
+}
+
+fn snapshot_hotkeys(
+    mut contexts: EguiContexts,
+    keys: Res<Input<KeyCode>>,
+    snapshot_path: Res<SnapshotPath>,
+    mut save_events: EventWriter<SaveSnapshot>,
+    mut load_events: EventWriter<LoadSnapshot>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if keys.just_pressed(KeyCode::F5) {
+        save_events.send(SaveSnapshot(PathBuf::from(&snapshot_path.0)));
+    }
+    if keys.just_pressed(KeyCode::F9) {
+        load_events.send(LoadSnapshot(PathBuf::from(&snapshot_path.0)));
+    }
+}
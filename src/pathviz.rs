@@ -2,8 +2,8 @@ use std::{collections::HashMap, time::Duration};
 
 use bevy::{
     prelude::{
-        Assets, Commands, Component, Handle, Image, IntoSystemConfigs, Plugin, Query, Res, ResMut,
-        Resource, Startup, Transform, Update, Vec3, With,
+        Assets, Commands, Component, DetectChanges, EventReader, Handle, Image, IntoSystemConfigs,
+        Plugin, Query, Res, ResMut, Resource, Startup, Transform, Update, Vec3, With,
     },
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     sprite::SpriteBundle,
@@ -12,10 +12,10 @@ use bevy::{
 
 use crate::{
     ant::{Ant, CurrentTask},
+    control::{SimControlPlugin, SimReset, SimSet, SimUpdate},
     grids::{add_map_to_grid_img, DecayGrid},
     utils::window_to_grid,
-    H, PH_UNIT_GRID_SIZE, VIZ_COLOR_STRENGTH, VIZ_COLOR_TO_FOOD, VIZ_COLOR_TO_HOME, VIZ_DECAY_RATE,
-    VIZ_MAX_COLOR_STRENGTH, W,
+    ResetSimulation, SimConfig,
 };
 
 #[derive(Component)]
@@ -28,10 +28,18 @@ pub struct PathVizGrid {
 }
 
 impl PathVizGrid {
-    fn new() -> Self {
+    fn new(config: &SimConfig) -> Self {
         Self {
-            dg_home: DecayGrid::new(HashMap::new(), VIZ_MAX_COLOR_STRENGTH),
-            dg_food: DecayGrid::new(HashMap::new(), VIZ_MAX_COLOR_STRENGTH),
+            dg_home: DecayGrid::new(
+                HashMap::new(),
+                config.viz_max_color_strength,
+                config.viz_decay(),
+            ),
+            dg_food: DecayGrid::new(
+                HashMap::new(),
+                config.viz_max_color_strength,
+                config.viz_decay(),
+            ),
         }
     }
 }
@@ -40,9 +48,16 @@ pub struct PathVizPlugin;
 
 impl Plugin for PathVizPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+        let config = app.world.resource::<SimConfig>().clone();
+
         app.add_systems(Startup, setup)
-            .insert_resource(PathVizGrid::new())
-            .add_systems(Update, update_grid_values)
+            .insert_resource(PathVizGrid::new(&config))
+            .add_event::<ResetSimulation>()
+            .add_systems(Update, reset_path_viz.in_set(SimReset))
+            .add_systems(SimUpdate, update_grid_values.after(SimSet::Ants))
             .add_systems(
                 Update,
                 update_path_viz_image.run_if(on_timer(Duration::from_secs_f32(0.1))),
@@ -50,56 +65,90 @@ impl Plugin for PathVizPlugin {
     }
 }
 
+fn reset_path_viz(
+    mut reset_events: EventReader<ResetSimulation>,
+    mut viz_grid: ResMut<PathVizGrid>,
+    mut render_query: Query<&mut Transform, With<PathVizImageRender>>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    *viz_grid = PathVizGrid::new(&config);
+    for mut transform in render_query.iter_mut() {
+        transform.scale = Vec3::splat(config.ph_unit_grid_size as f32);
+    }
+}
+
 ///这里更新路径数值
 fn update_grid_values(
     ant_query: Query<(&Transform, &CurrentTask), With<Ant>>,
     mut viz_grid: ResMut<PathVizGrid>,
+    config: Res<SimConfig>,
 ) {
+    let (w, h) = config.grid_img_size();
     for (transform, current_task) in ant_query.iter() {
-        let x = transform.translation.x as i32;
-        let y = transform.translation.y as i32;
-        let key = window_to_grid(x, y);
+        let x = transform.translation.x.floor() as i32;
+        let y = transform.translation.y.floor() as i32;
+        let key = window_to_grid(x, y, &config);
+        // 路径只画在 w x h 的图上，世界没有边界时图外的不记
+        if key.0 < 0 || key.1 < 0 || key.0 >= w as i32 || key.1 >= h as i32 {
+            continue;
+        }
 
         match current_task.0 {
             crate::ant::AntTask::FindFood => {
-                viz_grid.dg_food.add_value(&key, VIZ_COLOR_STRENGTH, 5.0);
+                viz_grid
+                    .dg_food
+                    .add_value(&key, config.viz_color_strength, 5.0);
             }
             crate::ant::AntTask::FindHome => {
-                viz_grid.dg_home.add_value(&key, VIZ_COLOR_STRENGTH, 5.0);
+                viz_grid
+                    .dg_home
+                    .add_value(&key, config.viz_color_strength, 5.0);
             }
         }
     }
 
-    viz_grid.dg_food.decay_values(VIZ_DECAY_RATE);
-    viz_grid.dg_food.drop_zero_values();
-    viz_grid.dg_home.decay_values(VIZ_DECAY_RATE);
-    viz_grid.dg_home.drop_zero_values();
+    let dt = 1.0 / config.tick_hz;
+    let viz_grid = &mut *viz_grid;
+    for grid in [&mut viz_grid.dg_food, &mut viz_grid.dg_home] {
+        if config.is_changed() {
+            grid.set_model(config.viz_decay());
+        }
+        grid.decay_values(dt);
+        grid.drop_zero_values();
+    }
 }
 
 ///这里根据路径数值开始渲染蚂蚁路径
 fn update_path_viz_image(
     mut textures: ResMut<Assets<Image>>,
     viz_grid: Res<PathVizGrid>,
-    mut query: Query<&mut Handle<Image>, With<PathVizImageRender>>,
+    mut query: Query<(&mut Handle<Image>, &mut Transform), With<PathVizImageRender>>,
+    config: Res<SimConfig>,
 ) {
-    let mut image_handle = query.single_mut();
-    let (w, h) = (
-        W as usize / PH_UNIT_GRID_SIZE,
-        H as usize / PH_UNIT_GRID_SIZE,
-    );
+    let (mut image_handle, mut transform) = query.single_mut();
+    let (w, h) = config.grid_img_size();
 
     let mut bytes = vec![0; w * h * 4];
     add_map_to_grid_img(
-        viz_grid.dg_food.get_values(),
-        &VIZ_COLOR_TO_FOOD,
+        viz_grid.dg_food.cells(),
+        &config.viz_color_to_food,
         &mut bytes,
         false,
+        config.viz_grid_opacity,
+        &config,
     );
     add_map_to_grid_img(
-        viz_grid.dg_home.get_values(),
-        &VIZ_COLOR_TO_HOME,
+        viz_grid.dg_home.cells(),
+        &config.viz_color_to_home,
         &mut bytes,
         false,
+        config.viz_grid_opacity,
+        &config,
     );
 
     let path_img = Image::new(
@@ -113,13 +162,15 @@ fn update_path_viz_image(
         TextureFormat::Rgba8Unorm,
     );
     *image_handle = textures.add(path_img);
+    // 读取快照后格子大小可能变了
+    transform.scale = Vec3::splat(config.ph_unit_grid_size as f32);
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
     commands.spawn((
         SpriteBundle {
             transform: Transform::from_xyz(0.0, 0.0, 1.0)
-                .with_scale(Vec3::splat(PH_UNIT_GRID_SIZE as f32)),
+                .with_scale(Vec3::splat(config.ph_unit_grid_size as f32)),
             ..Default::default()
         },
         PathVizImageRender,
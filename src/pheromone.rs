@@ -2,8 +2,9 @@ use std::{collections::HashMap, time::Duration};
 
 use bevy::{
     prelude::{
-        Assets, Commands, Component, Handle, Image, IntoSystemConfigs, Plugin, Query, Res, ResMut,
-        Resource, Startup, Transform, Update, Vec3, With,
+        Assets, Commands, Component, DetectChanges, Entity, EventReader, Handle, Image,
+        IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource, Startup, Transform, Update, Vec3,
+        With,
     },
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
     sprite::SpriteBundle,
@@ -11,94 +12,174 @@ use bevy::{
 };
 
 use crate::{
-    grids::{add_map_to_grid_img, WorldGrid},
-    FOOD_LOCATION, H, HOME_LOCATION, PH_COLOR_TO_FOOD, PH_COLOR_TO_HOME, PH_DECAY_INTERVAL,
-    PH_IMG_UPDATE_SEC, PH_KD_TREE_UPDATE_INTERVAL, W,
+    control::{sim_timer, SimControlPlugin, SimReset, SimSet, SimUpdate},
+    grids::{add_chunk_to_img, add_map_to_grid_img, WorldGrid},
+    obstacle::{ObstaclePlugin, Obstacles},
+    store::CHUNK_SIZE,
+    ColonySpec, GridBackend, ResetSimulation, SimConfig,
 };
 
-use crate::PH_UNIT_GRID_SIZE;
-
 #[derive(Default)]
 pub struct PheromonePlugin;
 
+/// 信息素图层渲染，无窗口模式下不添加
+pub struct PheromoneRenderPlugin;
+
 #[derive(Component)]
 struct PheromoneImageRender;
 
+/// 分块存储时每个已加载的块一张图，key 是块坐标
+#[derive(Resource, Default)]
+struct PheromoneChunkSprites(HashMap<(i32, i32), (Entity, Handle<Image>)>);
+
 impl Plugin for PheromonePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, setup)
-            .add_systems(
-                Update,
-                pheromone_decay.run_if(on_timer(Duration::from_secs_f32(PH_DECAY_INTERVAL))),
-            )
-            .add_systems(
-                Update,
-                clear_zero_signals.run_if(on_timer(Duration::from_secs_f32(2.0))),
-            )
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+        if !app.is_plugin_added::<ObstaclePlugin>() {
+            app.add_plugins(ObstaclePlugin);
+        }
+        let config = app.world.resource::<SimConfig>().clone();
+
+        app.add_event::<ResetSimulation>()
+            .add_systems(Update, reset_pheromones.in_set(SimReset))
             .add_systems(
-                Update,
-                update_kd_tree.run_if(on_timer(Duration::from_secs_f32(
-                    PH_KD_TREE_UPDATE_INTERVAL,
-                ))),
+                SimUpdate,
+                (
+                    pheromone_decay.run_if(sim_timer(|c| c.ph_decay_interval)),
+                    clear_zero_signals.run_if(sim_timer(|_| 2.0)),
+                )
+                    .chain()
+                    .in_set(SimSet::Pheromones),
             )
+            .insert_resource(Pheromones::new(&config));
+    }
+}
+
+impl Plugin for PheromoneRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<SimConfig>();
+        let config = app.world.resource::<SimConfig>().clone();
+
+        app.add_systems(Startup, setup)
+            .add_event::<ResetSimulation>()
+            .init_resource::<PheromoneChunkSprites>()
+            .add_systems(Update, reset_pheromone_image.in_set(SimReset))
             .add_systems(
                 Update,
-                pheromone_image_update.run_if(on_timer(Duration::from_secs_f32(PH_IMG_UPDATE_SEC))),
-            )
-            .insert_resource(Pheromones::new());
+                (pheromone_image_update, pheromone_chunk_update)
+                    .run_if(on_timer(Duration::from_secs_f32(config.ph_img_update_sec))),
+            );
     }
 }
 
-#[derive(Resource)]
-pub struct Pheromones {
+/// 一个蚁群私有的信息素图层，蚂蚁只跟随自己蚁群的信号
+pub struct ColonyPheromones {
     pub to_home: WorldGrid,
     pub to_food: WorldGrid,
 }
 
-fn pheromone_decay(mut pheronones: ResMut<Pheromones>) {
-    pheronones.to_food.decay_signals();
-    pheronones.to_home.decay_signals();
+/// 所有蚁群的信息素，下标和 [`SimConfig::colonies`] 一一对应
+#[derive(Resource)]
+pub struct Pheromones {
+    pub colonies: Vec<ColonyPheromones>,
+}
+
+fn pheromone_decay(
+    mut pheronones: ResMut<Pheromones>,
+    obstacles: Res<Obstacles>,
+    config: Res<SimConfig>,
+) {
+    let blocked = |key: &(i32, i32)| obstacles.blocks_cell(key);
+    for colony in pheronones.colonies.iter_mut() {
+        for grid in [&mut colony.to_food, &mut colony.to_home] {
+            // 衰减参数可以在运行中调整
+            if config.is_changed() {
+                grid.set_decay_model(config.ph_decay());
+            }
+            grid.decay_signals(config.ph_decay_interval);
+            grid.diffuse_signals(config.ph_diffusion_rate, config.ph_diffusion_loss, blocked);
+        }
+    }
 }
 
 fn clear_zero_signals(mut pheromones: ResMut<Pheromones>) {
-    pheromones.to_food.drop_zero_signals();
-    pheromones.to_home.drop_zero_signals();
+    for colony in pheromones.colonies.iter_mut() {
+        colony.to_food.drop_zero_signals();
+        colony.to_home.drop_zero_signals();
+    }
 }
 
-fn update_kd_tree(mut pheromones: ResMut<Pheromones>) {
-    pheromones.update_tree()
+fn reset_pheromones(
+    mut reset_events: EventReader<ResetSimulation>,
+    mut pheromones: ResMut<Pheromones>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    *pheromones = Pheromones::new(&config);
 }
 
-impl Pheromones {
-    fn new() -> Self {
-        let mut to_food_map = HashMap::new();
-        let mut to_home_map = HashMap::new();
+fn reset_pheromone_image(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetSimulation>,
+    mut render_query: Query<&mut Transform, With<PheromoneImageRender>>,
+    mut chunk_sprites: ResMut<PheromoneChunkSprites>,
+    config: Res<SimConfig>,
+) {
+    if reset_events.is_empty() {
+        return;
+    }
+    reset_events.clear();
+
+    // 格子大小可能变了，块的位置要重新算
+    for (_, (entity, _)) in chunk_sprites.0.drain() {
+        commands.entity(entity).despawn();
+    }
 
-        to_food_map.insert((FOOD_LOCATION.0 as i32, FOOD_LOCATION.1 as i32), 100000.0);
-        to_home_map.insert((HOME_LOCATION.0 as i32, HOME_LOCATION.1 as i32), 100000.0);
+    for mut transform in render_query.iter_mut() {
+        transform.scale = Vec3::splat(config.ph_unit_grid_size as f32);
+    }
+}
 
+impl ColonyPheromones {
+    /// 图层一开始是空的，蚂蚁靠 `ant_target_auto_pull_radius` 找到附近的食物和巢穴
+    fn new(colony: &ColonySpec, config: &SimConfig) -> Self {
         Self {
-            to_food: WorldGrid::new(PH_COLOR_TO_FOOD, to_food_map),
-            to_home: WorldGrid::new(PH_COLOR_TO_HOME, to_home_map),
+            to_food: WorldGrid::new(colony.ph_color_to_food, HashMap::new(), config),
+            to_home: WorldGrid::new(colony.ph_color_to_home, HashMap::new(), config),
         }
     }
+}
 
-    fn update_tree(&mut self) {
-        self.to_food.update_tree();
-        self.to_home.update_tree();
+impl Pheromones {
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            colonies: config
+                .colonies
+                .iter()
+                .map(|colony| ColonyPheromones::new(colony, config))
+                .collect(),
+        }
     }
 
     pub fn clear_steer_cache(&mut self) {
-        self.to_food.clear_steer_cache();
-        self.to_home.clear_steer_cache();
+        for colony in self.colonies.iter_mut() {
+            colony.to_food.clear_steer_cache();
+            colony.to_home.clear_steer_cache();
+        }
     }
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, config: Res<SimConfig>) {
     commands.spawn((
         SpriteBundle {
             transform: Transform::from_xyz(0.0, 0.0, 0.0)
-                .with_scale(Vec3::splat(PH_UNIT_GRID_SIZE as f32)),
+                .with_scale(Vec3::splat(config.ph_unit_grid_size as f32)),
             ..Default::default()
         },
         PheromoneImageRender,
@@ -108,28 +189,37 @@ fn setup(mut commands: Commands) {
 fn pheromone_image_update(
     mut textures: ResMut<Assets<Image>>,
     pheromone: Res<Pheromones>,
-    mut image_handle_query: Query<&mut Handle<Image>, With<PheromoneImageRender>>,
+    mut image_handle_query: Query<(&mut Handle<Image>, &mut Transform), With<PheromoneImageRender>>,
+    config: Res<SimConfig>,
 ) {
-    let mut img_handle = image_handle_query.single_mut();
-    let (w, h) = (
-        W as usize / PH_UNIT_GRID_SIZE,
-        H as usize / PH_UNIT_GRID_SIZE,
-    );
+    let (mut img_handle, mut transform) = image_handle_query.single_mut();
+    let (w, h) = config.grid_img_size();
     let mut bytes = vec![0; w * h * 4];
 
-    add_map_to_grid_img(
-        pheromone.to_home.get_signals(),
-        &pheromone.to_home.color,
-        &mut bytes,
-        true,
-    );
+    // 分块存储由 pheromone_chunk_update 按块画
+    let colonies = match config.ph_grid_backend {
+        GridBackend::Chunked => &[],
+        _ => &pheromone.colonies[..],
+    };
+    for colony in colonies.iter() {
+        add_map_to_grid_img(
+            colony.to_home.signals(),
+            &colony.to_home.color,
+            &mut bytes,
+            true,
+            config.ph_grid_opacity,
+            &config,
+        );
 
-    add_map_to_grid_img(
-        pheromone.to_food.get_signals(),
-        &pheromone.to_food.color,
-        &mut bytes,
-        true,
-    );
+        add_map_to_grid_img(
+            colony.to_food.signals(),
+            &colony.to_food.color,
+            &mut bytes,
+            true,
+            config.ph_grid_opacity,
+            &config,
+        );
+    }
 
     let pheronone_map = Image::new(
         Extent3d {
@@ -142,4 +232,73 @@ fn pheromone_image_update(
         TextureFormat::Rgba8Unorm,
     );
     *img_handle = textures.add(pheronone_map);
+    // 读取快照后格子大小可能变了
+    transform.scale = Vec3::splat(config.ph_unit_grid_size as f32);
+}
+
+/// 分块存储时只画已加载的块，每块一张 CHUNK_SIZE x CHUNK_SIZE 的图，世界没有边界也不用一整张大图
+fn pheromone_chunk_update(
+    mut commands: Commands,
+    mut textures: ResMut<Assets<Image>>,
+    pheromone: Res<Pheromones>,
+    mut chunk_sprites: ResMut<PheromoneChunkSprites>,
+    config: Res<SimConfig>,
+) {
+    let size = CHUNK_SIZE as usize;
+    let mut tiles: HashMap<(i32, i32), Vec<u8>> = HashMap::new();
+    for colony in pheromone.colonies.iter() {
+        for grid in [&colony.to_home, &colony.to_food] {
+            let Some(chunks) = grid.chunks() else {
+                continue;
+            };
+            for (chunk, values) in chunks {
+                let bytes = tiles
+                    .entry(chunk)
+                    .or_insert_with(|| vec![0; size * size * 4]);
+                add_chunk_to_img(values, &grid.color, bytes, config.ph_grid_opacity, &config);
+            }
+        }
+    }
+
+    // 信号消失、块被释放后图也删掉
+    chunk_sprites.0.retain(|chunk, (entity, _)| {
+        let loaded = tiles.contains_key(chunk);
+        if !loaded {
+            commands.entity(*entity).despawn();
+        }
+        loaded
+    });
+
+    let unit = config.ph_unit_grid_size as f32;
+    for (chunk, bytes) in tiles {
+        if let Some((_, handle)) = chunk_sprites.0.get(&chunk) {
+            if let Some(image) = textures.get_mut(handle) {
+                image.data = bytes;
+            }
+            continue;
+        }
+
+        let image = Image::new(
+            Extent3d {
+                width: size as u32,
+                height: size as u32,
+                ..Default::default()
+            },
+            TextureDimension::D2,
+            bytes,
+            TextureFormat::Rgba8Unorm,
+        );
+        let handle = textures.add(image);
+        // 格子 k 覆盖世界坐标 [k * unit, (k + 1) * unit)
+        let center = |c: i32| (c * CHUNK_SIZE + CHUNK_SIZE / 2) as f32 * unit;
+        let entity = commands
+            .spawn(SpriteBundle {
+                texture: handle.clone(),
+                transform: Transform::from_xyz(center(chunk.0), center(chunk.1), 0.0)
+                    .with_scale(Vec3::splat(unit)),
+                ..Default::default()
+            })
+            .id();
+        chunk_sprites.0.insert(chunk, (entity, handle));
+    }
 }
@@ -0,0 +1,798 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    log::warn,
+    math::{vec2, EulerRot},
+    prelude::{
+        DetectChanges, IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource, Transform, Vec2,
+        With,
+    },
+};
+
+use crate::{
+    ant::{Ant, AntTask, CurrentTask},
+    colony::{Colony, ColonyId},
+    control::{SimClock, SimControlPlugin, SimSet, SimUpdate},
+    food::FoodSource,
+    obstacle::Obstacles,
+    pheromone::Pheromones,
+    SimConfig,
+};
+
+/// 录像文件开头的标记
+const MAGIC: &[u8; 6] = b"ANTREC";
+
+/// 录像格式版本，帧结构有不兼容的改动时加一
+pub const RECORDING_VERSION: u16 = 1;
+
+/// 帧标记：这一帧带信息素
+const FRAME_PHEROMONES: u8 = 1;
+/// 帧标记：这一帧带障碍物（编辑器改过墙之后）
+const FRAME_OBSTACLES: u8 = 2;
+
+/// 每帧开头 tick (u64)、elapsed (f64) 和帧标记 (u8) 的长度
+const FRAME_HEADER_BYTES: usize = 17;
+/// 一只蚂蚁：坐标 2 x i16、角度 u16、蚁群 u8、任务 u8
+const ANT_BYTES: usize = 8;
+/// 一个蚁巢：id u32、位置 2 x f32、半径、库存 f32、送达数 u32
+const COLONY_BYTES: usize = 24;
+/// 一处食物：位置 2 x f32、半径、剩余量、初始量 f32
+const FOOD_BYTES: usize = 20;
+/// 一个信息素格子：坐标 2 x i16、信号 u16
+const CELL_BYTES: usize = 6;
+
+/// 把每一步蚂蚁、蚁巢、食物的状态写进紧凑的二进制录像，信息素图层每隔
+/// `ph_img_update_sec` 模拟秒记一次；障碍物记在文件头，之后每次改动再记一次
+pub struct RecorderPlugin {
+    path: PathBuf,
+    every: u64,
+}
+
+impl RecorderPlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            every: 1,
+        }
+    }
+
+    /// 每隔 n 步记一帧
+    pub fn every(mut self, n: u64) -> Self {
+        self.every = n.max(1);
+        self
+    }
+}
+
+#[derive(Resource)]
+struct Recorder {
+    path: PathBuf,
+    every: u64,
+    writer: Option<BufWriter<File>>,
+    /// 上次记录信息素的模拟时间
+    last_pheromones: Option<f64>,
+    /// 障碍物改过、还没有记下来；跳过的步里的改动也不会丢
+    obstacles_changed: bool,
+    failed: bool,
+}
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<SimControlPlugin>() {
+            app.add_plugins(SimControlPlugin);
+        }
+
+        app.insert_resource(Recorder {
+            path: self.path.clone(),
+            every: self.every,
+            writer: None,
+            last_pheromones: None,
+            obstacles_changed: false,
+            failed: false,
+        })
+        .add_systems(SimUpdate, record_frame.after(SimSet::Pheromones));
+    }
+}
+
+///文件头在第一帧时才写，这时配置和地图都已经加载好了
+#[allow(clippy::too_many_arguments)]
+fn record_frame(
+    mut recorder: ResMut<Recorder>,
+    clock: Res<SimClock>,
+    ant_query: Query<(&Transform, &CurrentTask, &ColonyId), With<Ant>>,
+    colony_query: Query<&Colony>,
+    food_query: Query<&FoodSource>,
+    pheromones: Res<Pheromones>,
+    obstacles: Res<Obstacles>,
+    config: Res<SimConfig>,
+) {
+    if recorder.failed {
+        return;
+    }
+    if obstacles.is_changed() {
+        recorder.obstacles_changed = true;
+    }
+    if clock.tick % recorder.every != 0 {
+        return;
+    }
+    // 坐标按 w x h 量化，走出范围的蚂蚁会被压到边上；重新加载配置后也可能变成无边界
+    if config.world_unbounded {
+        warn!(
+            "recording to {} stopped: the world is unbounded",
+            recorder.path.display()
+        );
+        recorder.failed = true;
+        return;
+    }
+
+    let with_pheromones = recorder.last_pheromones.map_or(true, |last| {
+        clock.elapsed - last >= config.ph_img_update_sec as f64
+    });
+    // 第一帧之前的改动已经在文件头里了
+    let with_obstacles = recorder.obstacles_changed && recorder.writer.is_some();
+    recorder.obstacles_changed = false;
+    let mut flags = 0;
+    if with_pheromones {
+        flags |= FRAME_PHEROMONES;
+    }
+    if with_obstacles {
+        flags |= FRAME_OBSTACLES;
+    }
+    let mut frame = ByteWriter::default();
+    frame.u64(clock.tick);
+    frame.f64(clock.elapsed);
+    frame.u8(flags);
+
+    frame.count(ant_query.iter().len());
+    for (transform, task, colony) in ant_query.iter() {
+        let (angle, _, _) = transform.rotation.to_euler(EulerRot::ZYX);
+        frame.i16(quantize_coord(transform.translation.x, config.w / 2.0));
+        frame.i16(quantize_coord(transform.translation.y, config.h / 2.0));
+        frame.u16(quantize(
+            angle + std::f32::consts::PI,
+            std::f32::consts::TAU,
+        ));
+        frame.u8(colony.0 as u8);
+        frame.u8(task.0 as u8);
+    }
+    frame.count(colony_query.iter().len());
+    for colony in colony_query.iter() {
+        frame.u32(colony.id.0 as u32);
+        frame.vec2(colony.pos);
+        frame.f32(colony.radius);
+        frame.f32(colony.stockpile);
+        frame.u32(colony.delivered);
+    }
+    frame.count(food_query.iter().len());
+    for food in food_query.iter() {
+        frame.vec2(food.pos);
+        frame.f32(food.radius);
+        frame.f32(food.amount);
+        frame.f32(food.initial_amount);
+    }
+    if with_pheromones {
+        recorder.last_pheromones = Some(clock.elapsed);
+        frame.count(pheromones.colonies.len());
+        for colony in pheromones.colonies.iter() {
+            for grid in [&colony.to_home, &colony.to_food] {
+                frame.cells(grid.signals(), |v| {
+                    quantize(v, config.max_pheromone_strength)
+                });
+            }
+        }
+    }
+    if with_obstacles {
+        frame.obstacles(&obstacles);
+    }
+
+    if let Err(e) = recorder.write(&frame.0, &config, &obstacles) {
+        warn!("recording to {} stopped: {e}", recorder.path.display());
+        recorder.failed = true;
+    }
+}
+
+impl Recorder {
+    fn write(
+        &mut self,
+        frame: &[u8],
+        config: &SimConfig,
+        obstacles: &Obstacles,
+    ) -> Result<(), RecordingError> {
+        if self.writer.is_none() {
+            let mut writer = BufWriter::new(File::create(&self.path).map_err(RecordingError::Io)?);
+            writer
+                .write_all(&header(config, obstacles)?)
+                .map_err(RecordingError::Io)?;
+            self.writer = Some(writer);
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+
+        // 每帧都 flush，程序直接退出时录像也是完整的
+        writer
+            .write_all(&(frame.len() as u32).to_le_bytes())
+            .and_then(|_| writer.write_all(frame))
+            .and_then(|_| writer.flush())
+            .map_err(RecordingError::Io)
+    }
+}
+
+fn header(config: &SimConfig, obstacles: &Obstacles) -> Result<Vec<u8>, RecordingError> {
+    let config = ron::to_string(config).map_err(RecordingError::Serialize)?;
+    let mut header = ByteWriter::default();
+    header.0.extend_from_slice(MAGIC);
+    header.u16(RECORDING_VERSION);
+    header.count(config.len());
+    header.0.extend_from_slice(config.as_bytes());
+    header.obstacles(obstacles);
+    Ok(header.0)
+}
+
+/// 把 [0, max] 的值映射到 u16
+fn quantize(value: f32, max: f32) -> u16 {
+    ((value / max).clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+fn dequantize(value: u16, max: f32) -> f32 {
+    value as f32 / u16::MAX as f32 * max
+}
+
+/// 蚂蚁坐标按世界大小映射到 i16，1920 宽的世界精度约 0.03 像素
+fn quantize_coord(value: f32, half_size: f32) -> i16 {
+    ((value / half_size).clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn dequantize_coord(value: i16, half_size: f32) -> f32 {
+    value as f32 / i16::MAX as f32 * half_size
+}
+
+/// 格子坐标存成 i16，世界再大也不会超出
+fn grid_key((x, y): (i32, i32)) -> Option<(i16, i16)> {
+    Some((i16::try_from(x).ok()?, i16::try_from(y).ok()?))
+}
+
+#[derive(Default)]
+struct ByteWriter(Vec<u8>);
+
+impl ByteWriter {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i16(&mut self, v: i16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn vec2(&mut self, v: Vec2) {
+        self.f32(v.x);
+        self.f32(v.y);
+    }
+
+    fn count(&mut self, count: usize) {
+        self.u32(count as u32);
+    }
+
+    fn cells(
+        &mut self,
+        cells: impl Iterator<Item = ((i32, i32), f32)>,
+        value: impl Fn(f32) -> u16,
+    ) {
+        let mut cells: Vec<_> = cells
+            .filter_map(|(k, v)| Some((grid_key(k)?, value(v))))
+            .collect();
+        cells.sort_by_key(|(k, _)| *k);
+        self.count(cells.len());
+        for ((x, y), v) in cells {
+            self.i16(x);
+            self.i16(y);
+            self.u16(v);
+        }
+    }
+
+    /// 阻挡格子和地形格子，都按格子排好序
+    fn obstacles(&mut self, obstacles: &Obstacles) {
+        let mut blocked: Vec<_> = obstacles.cells().filter_map(|&k| grid_key(k)).collect();
+        blocked.sort();
+        self.count(blocked.len());
+        for (x, y) in blocked {
+            self.i16(x);
+            self.i16(y);
+        }
+        let mut terrain: Vec<_> = obstacles
+            .terrain_cells()
+            .filter_map(|(&k, &speed)| Some((grid_key(k)?, speed)))
+            .collect();
+        terrain.sort_by_key(|(k, _)| *k);
+        self.count(terrain.len());
+        for ((x, y), speed) in terrain {
+            self.i16(x);
+            self.i16(y);
+            self.f32(speed);
+        }
+    }
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], RecordingError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.data.len());
+        let Some(end) = end else {
+            return Err(RecordingError::Format("unexpected end of data"));
+        };
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RecordingError> {
+        Ok(self.bytes(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    fn u8(&mut self) -> Result<u8, RecordingError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, RecordingError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, RecordingError> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, RecordingError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, RecordingError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, RecordingError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, RecordingError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, RecordingError> {
+        Ok(vec2(self.f32()?, self.f32()?))
+    }
+
+    fn count(&mut self) -> Result<usize, RecordingError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn cells(&mut self, value: impl Fn(u16) -> f32) -> Result<RecordedCells, RecordingError> {
+        (0..self.count()?)
+            .map(|_| Ok(((self.i16()? as i32, self.i16()? as i32), value(self.u16()?))))
+            .collect()
+    }
+
+    /// 跳过 count 个 size 字节的记录，count 在前面
+    fn skip_records(&mut self, size: usize) -> Result<(), RecordingError> {
+        let count = self.count()?;
+        let len = count
+            .checked_mul(size)
+            .ok_or(RecordingError::Format("record count overflows"))?;
+        self.bytes(len).map(|_| ())
+    }
+
+    fn obstacles(&mut self, config: &SimConfig) -> Result<Obstacles, RecordingError> {
+        let blocked = (0..self.count()?)
+            .map(|_| Ok((self.i16()? as i32, self.i16()? as i32)))
+            .collect::<Result<Vec<_>, RecordingError>>()?;
+        let terrain = (0..self.count()?)
+            .map(|_| Ok(((self.i16()? as i32, self.i16()? as i32), self.f32()?)))
+            .collect::<Result<Vec<_>, RecordingError>>()?;
+        Ok(Obstacles::from_cells(config, blocked, terrain))
+    }
+}
+
+/// 录像里的一只蚂蚁
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedAnt {
+    pub pos: Vec2,
+    /// 贴图绕 z 轴的旋转角
+    pub angle: f32,
+    pub colony: ColonyId,
+    pub task: AntTask,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedColony {
+    pub id: ColonyId,
+    pub pos: Vec2,
+    pub radius: f32,
+    pub stockpile: f32,
+    pub delivered: u32,
+}
+
+/// 录像的一帧，不含信息素（信息素用 [`Recording::pheromones`] 单独读取）
+#[derive(Debug, Clone)]
+pub struct RecordedFrame {
+    pub tick: u64,
+    pub elapsed: f64,
+    pub ants: Vec<RecordedAnt>,
+    pub colonies: Vec<RecordedColony>,
+    pub food: Vec<FoodSource>,
+}
+
+/// 一个图层的 (格子, 信号)
+pub type RecordedCells = Vec<((i32, i32), f32)>;
+
+/// 每个蚁群的 (to_home, to_food) 信号
+pub type RecordedPheromones = Vec<(RecordedCells, RecordedCells)>;
+
+#[derive(Debug, Clone, Copy)]
+struct FrameIndex {
+    offset: usize,
+    tick: u64,
+    elapsed: f64,
+    pheromones: bool,
+    obstacles: bool,
+}
+
+/// 读进内存的录像，建好每一帧的索引，可以随意跳转
+#[derive(Resource)]
+pub struct Recording {
+    pub config: SimConfig,
+    /// 文件头里的障碍物，录制过程中的改动见 [`Recording::obstacles_at`]
+    pub obstacles: Obstacles,
+    data: Vec<u8>,
+    frames: Vec<FrameIndex>,
+}
+
+impl Recording {
+    /// 读取录像；最后一帧没写完（比如程序中途被杀掉）时忽略这一帧
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let data = fs::read(path).map_err(RecordingError::Io)?;
+        let mut reader = ByteReader::new(&data);
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(RecordingError::Format("not a recording"));
+        }
+        let version = reader.u16()?;
+        if version != RECORDING_VERSION {
+            return Err(RecordingError::Version {
+                found: version,
+                expected: RECORDING_VERSION,
+            });
+        }
+        let config_len = reader.count()?;
+        let config = std::str::from_utf8(reader.bytes(config_len)?)
+            .map_err(|_| RecordingError::Format("config is not utf-8"))?;
+        let config: SimConfig = ron::from_str(config).map_err(RecordingError::Parse)?;
+
+        let obstacles = reader.obstacles(&config)?;
+
+        let mut frames = Vec::new();
+        while let Ok(len) = reader.count() {
+            let Ok(frame) = reader.bytes(len) else {
+                break;
+            };
+            let mut frame_reader = ByteReader::new(frame);
+            let (Ok(tick), Ok(elapsed), Ok(flags)) =
+                (frame_reader.u64(), frame_reader.f64(), frame_reader.u8())
+            else {
+                break;
+            };
+            frames.push(FrameIndex {
+                offset: reader.pos - len,
+                tick,
+                elapsed,
+                pheromones: flags & FRAME_PHEROMONES != 0,
+                obstacles: flags & FRAME_OBSTACLES != 0,
+            });
+        }
+        Ok(Self {
+            config,
+            obstacles,
+            data,
+            frames,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn tick(&self, index: usize) -> u64 {
+        self.frames[index].tick
+    }
+
+    pub fn elapsed(&self, index: usize) -> f64 {
+        self.frames[index].elapsed
+    }
+
+    /// 模拟时间不晚于 elapsed 的最后一帧
+    pub fn frame_at(&self, elapsed: f64) -> usize {
+        self.frames
+            .partition_point(|frame| frame.elapsed <= elapsed)
+            .saturating_sub(1)
+    }
+
+    /// 不晚于 index 的、带信息素的最后一帧
+    pub fn pheromone_frame(&self, index: usize) -> Option<usize> {
+        self.frames[..=index]
+            .iter()
+            .rposition(|frame| frame.pheromones)
+    }
+
+    /// 不晚于 index 的、带障碍物的最后一帧，录制中没有改过墙时为 None
+    pub fn obstacle_frame(&self, index: usize) -> Option<usize> {
+        self.frames[..=index]
+            .iter()
+            .rposition(|frame| frame.obstacles)
+    }
+
+    fn reader(&self, index: usize) -> ByteReader<'_> {
+        let mut reader = ByteReader::new(&self.data);
+        reader.pos = self.frames[index].offset + FRAME_HEADER_BYTES;
+        reader
+    }
+
+    /// 跳过蚂蚁、蚁巢和食物，停在信息素（或障碍物）开始的地方
+    fn skip_entities(&self, index: usize) -> Result<ByteReader<'_>, RecordingError> {
+        let mut reader = self.reader(index);
+        reader.skip_records(ANT_BYTES)?;
+        reader.skip_records(COLONY_BYTES)?;
+        reader.skip_records(FOOD_BYTES)?;
+        Ok(reader)
+    }
+
+    pub fn frame(&self, index: usize) -> Result<RecordedFrame, RecordingError> {
+        let mut reader = self.reader(index);
+        let (half_w, half_h) = (self.config.w / 2.0, self.config.h / 2.0);
+        let ants = (0..reader.count()?)
+            .map(|_| {
+                Ok(RecordedAnt {
+                    pos: vec2(
+                        dequantize_coord(reader.i16()?, half_w),
+                        dequantize_coord(reader.i16()?, half_h),
+                    ),
+                    angle: dequantize(reader.u16()?, std::f32::consts::TAU) - std::f32::consts::PI,
+                    colony: ColonyId(reader.u8()? as usize),
+                    task: match reader.u8()? {
+                        0 => AntTask::FindFood,
+                        _ => AntTask::FindHome,
+                    },
+                })
+            })
+            .collect::<Result<_, RecordingError>>()?;
+        let colonies = (0..reader.count()?)
+            .map(|_| {
+                Ok(RecordedColony {
+                    id: ColonyId(reader.u32()? as usize),
+                    pos: reader.vec2()?,
+                    radius: reader.f32()?,
+                    stockpile: reader.f32()?,
+                    delivered: reader.u32()?,
+                })
+            })
+            .collect::<Result<_, RecordingError>>()?;
+        let food = (0..reader.count()?)
+            .map(|_| {
+                Ok(FoodSource {
+                    pos: reader.vec2()?,
+                    radius: reader.f32()?,
+                    amount: reader.f32()?,
+                    initial_amount: reader.f32()?,
+                })
+            })
+            .collect::<Result<_, RecordingError>>()?;
+
+        Ok(RecordedFrame {
+            tick: self.frames[index].tick,
+            elapsed: self.frames[index].elapsed,
+            ants,
+            colonies,
+            food,
+        })
+    }
+
+    /// index 这一帧记录的信息素，这一帧没有记录时返回 None
+    pub fn pheromones(&self, index: usize) -> Result<Option<RecordedPheromones>, RecordingError> {
+        if !self.frames[index].pheromones {
+            return Ok(None);
+        }
+
+        let mut reader = self.skip_entities(index)?;
+        let max = self.config.max_pheromone_strength;
+        let layers = (0..reader.count()?)
+            .map(|_| {
+                let to_home = reader.cells(|v| dequantize(v, max))?;
+                let to_food = reader.cells(|v| dequantize(v, max))?;
+                Ok((to_home, to_food))
+            })
+            .collect::<Result<_, RecordingError>>()?;
+        Ok(Some(layers))
+    }
+
+    /// index 这一帧时的障碍物：之前最后一次记录的改动，没有改过时就是文件头里的
+    pub fn obstacles_at(&self, index: usize) -> Result<Obstacles, RecordingError> {
+        let Some(frame) = self.obstacle_frame(index) else {
+            return Ok(self.obstacles.clone());
+        };
+        let mut reader = self.skip_entities(frame)?;
+        if self.frames[frame].pheromones {
+            for _ in 0..reader.count()? * 2 {
+                reader.skip_records(CELL_BYTES)?;
+            }
+        }
+        reader.obstacles(&self.config)
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Format(&'static str),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    Version { found: u16, expected: u16 },
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "failed to access recording: {e}"),
+            RecordingError::Format(reason) => write!(f, "invalid recording: {reason}"),
+            RecordingError::Parse(e) => write!(f, "failed to parse recorded config: {e}"),
+            RecordingError::Serialize(e) => write!(f, "failed to write recorded config: {e}"),
+            RecordingError::Version { found, expected } => write!(
+                f,
+                "recording version {found} is not supported (expected {expected})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{headless, ShapeSpec, TerrainSpec};
+
+    fn assert_cells_eq(recorded: &RecordedCells, grid: &crate::grids::WorldGrid, max: f32) {
+        let recorded: HashMap<_, _> = recorded.iter().copied().collect();
+        let signals: HashMap<_, _> = grid.signals().collect();
+        assert_eq!(recorded.len(), signals.len());
+        for (key, value) in signals {
+            let step = max / u16::MAX as f32;
+            assert!((recorded[&key] - value.min(max)).abs() <= step, "{key:?}");
+        }
+    }
+
+    /// 录 60 步，中途改一次墙，读回来的最后一帧要和 World 里的一致
+    #[test]
+    fn recording_round_trip() {
+        let path = std::env::temp_dir().join(format!("ants-recording-{}.bin", std::process::id()));
+        let config = SimConfig {
+            seed: Some(7),
+            num_ants: 200,
+            ph_img_update_sec: 0.0,
+            terrain: vec![TerrainSpec {
+                shape: ShapeSpec::Circle {
+                    center: (-200.0, 100.0),
+                    radius: 30.0,
+                },
+                speed: 0.5,
+            }],
+            ..Default::default()
+        };
+        let mut app = headless::headless_app(config.clone());
+        app.add_plugins(RecorderPlugin::new(&path));
+        let mut app = headless::run_app(app, 30);
+        app.world.resource_mut::<Obstacles>().block((-3, 7));
+        let mut app = headless::run_app(app, 30);
+
+        let recording = Recording::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(recording.len(), 60);
+        let last = recording.len() - 1;
+
+        let frame = recording.frame(last).unwrap();
+        assert_eq!(frame.tick, app.world.resource::<SimClock>().tick);
+        let ants: Vec<_> = app
+            .world
+            .query_filtered::<(&Transform, &CurrentTask, &ColonyId), With<Ant>>()
+            .iter(&app.world)
+            .map(|(t, task, colony)| (t.translation.truncate(), task.0, colony.0))
+            .collect();
+        assert_eq!(frame.ants.len(), ants.len());
+        let step = config.w / 2.0 / i16::MAX as f32;
+        for (recorded, (pos, task, colony)) in frame.ants.iter().zip(ants) {
+            assert!(recorded.pos.distance(pos) <= step * 2.0);
+            assert_eq!(recorded.task, task);
+            assert_eq!(recorded.colony.0, colony);
+        }
+        let colonies: Vec<_> = app
+            .world
+            .query::<&Colony>()
+            .iter(&app.world)
+            .cloned()
+            .collect();
+        assert_eq!(frame.colonies.len(), colonies.len());
+        for (recorded, colony) in frame.colonies.iter().zip(colonies) {
+            assert_eq!(recorded.pos, colony.pos);
+            assert_eq!(recorded.stockpile, colony.stockpile);
+            assert_eq!(recorded.delivered, colony.delivered);
+        }
+        let food: Vec<_> = app
+            .world
+            .query::<&FoodSource>()
+            .iter(&app.world)
+            .cloned()
+            .collect();
+        assert_eq!(frame.food.len(), food.len());
+        for (recorded, food) in frame.food.iter().zip(food) {
+            assert_eq!(recorded.pos, food.pos);
+            assert_eq!(recorded.amount, food.amount);
+        }
+
+        // 信息素记在蚂蚁、蚁巢、食物后面，读得出来说明前面跳过的长度是对的
+        let layers = recording.pheromones(last).unwrap().unwrap();
+        let pheromones = app.world.resource::<Pheromones>();
+        assert_eq!(layers.len(), pheromones.colonies.len());
+        assert!(layers.iter().any(|(h, f)| !h.is_empty() || !f.is_empty()));
+        for ((to_home, to_food), colony) in layers.iter().zip(pheromones.colonies.iter()) {
+            assert_cells_eq(to_home, &colony.to_home, config.max_pheromone_strength);
+            assert_cells_eq(to_food, &colony.to_food, config.max_pheromone_strength);
+        }
+
+        assert!(recording.obstacles.terrain_cells().next().is_some());
+        assert!(!recording.obstacles_at(0).unwrap().is_blocked_cell(&(-3, 7)));
+        let obstacle_frame = recording.obstacle_frame(last).unwrap();
+        assert!(obstacle_frame > 0);
+        let obstacles = recording.obstacles_at(last).unwrap();
+        assert!(obstacles.is_blocked_cell(&(-3, 7)));
+        assert_eq!(
+            obstacles.terrain_cells().count(),
+            recording.obstacles.terrain_cells().count()
+        );
+    }
+}
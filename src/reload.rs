@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bevy::{
+    log::{info, warn},
+    prelude::{EventWriter, IntoSystemConfigs, Plugin, Query, ResMut, Resource, Update},
+    time::common_conditions::on_timer,
+    window::Window,
+};
+
+use crate::{control::SimReset, ConfigOverrides, ResetSimulation, SimConfig};
+
+/// 配置文件检查间隔（秒）
+const CONFIG_POLL_INTERVAL: f32 = 1.0;
+
+/// 监视配置文件，文件改动后把新参数应用到正在运行的模拟
+pub struct ConfigReloadPlugin {
+    path: PathBuf,
+    overrides: ConfigOverrides,
+}
+
+impl ConfigReloadPlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            overrides: ConfigOverrides::default(),
+        }
+    }
+
+    /// 命令行的覆盖，每次重新加载后都会再应用
+    pub fn with_overrides(mut self, overrides: ConfigOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+}
+
+#[derive(Resource)]
+struct WatchedConfig {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    overrides: ConfigOverrides,
+}
+
+impl Plugin for ConfigReloadPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<SimConfig>()
+            .add_event::<ResetSimulation>()
+            .insert_resource(WatchedConfig {
+                path: self.path.clone(),
+                modified: modified_time(&self.path),
+                overrides: self.overrides.clone(),
+            })
+            .add_systems(
+                Update,
+                reload_config
+                    .run_if(on_timer(Duration::from_secs_f32(CONFIG_POLL_INTERVAL)))
+                    .before(SimReset),
+            );
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+///文件有改动时重新加载，校验失败则保留旧配置
+fn reload_config(
+    mut watched: ResMut<WatchedConfig>,
+    mut config: ResMut<SimConfig>,
+    mut reset_events: EventWriter<ResetSimulation>,
+    mut windows: Query<&mut Window>,
+) {
+    let modified = modified_time(&watched.path);
+    if modified.is_none() || modified == watched.modified {
+        return;
+    }
+    watched.modified = modified;
+
+    let new_config = SimConfig::load(&watched.path).and_then(|mut c| {
+        watched.overrides.apply(&mut c)?;
+        Ok(c)
+    });
+    let new_config = match new_config {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("config {} not reloaded: {e}", watched.path.display());
+            return;
+        }
+    };
+    if new_config == *config {
+        return;
+    }
+
+    let needs_reset = config.needs_reset(&new_config);
+    if new_config.w != config.w || new_config.h != config.h {
+        for mut window in windows.iter_mut() {
+            window.resolution.set(new_config.w, new_config.h);
+        }
+    }
+
+    *config = new_config;
+    if needs_reset {
+        info!(
+            "config {} reloaded, resetting simulation",
+            watched.path.display()
+        );
+        reset_events.send(ResetSimulation);
+    } else {
+        info!("config {} reloaded", watched.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{App, Events};
+
+    use super::*;
+
+    /// 文件改了 ant_speed，命令行的种子和地图要留下，也不应该重置
+    #[test]
+    fn reload_keeps_overrides() {
+        let path = std::env::temp_dir().join(format!("ants-reload-{}.ron", std::process::id()));
+        fs::write(&path, "(num_ants: 10)").unwrap();
+        let overrides = ConfigOverrides {
+            seed: Some(42),
+            map: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/maps/open_field.png").into()),
+            steering: None,
+        };
+        let mut config = SimConfig::load(&path).unwrap();
+        overrides.apply(&mut config).unwrap();
+
+        let mut app = App::new();
+        app.insert_resource(config.clone())
+            .add_event::<ResetSimulation>()
+            .insert_resource(WatchedConfig {
+                path: path.clone(),
+                modified: None,
+                overrides: overrides.clone(),
+            })
+            .add_systems(Update, reload_config);
+        fs::write(&path, "(num_ants: 10, ant_speed: 120.0)").unwrap();
+        app.update();
+        fs::remove_file(&path).ok();
+
+        let reloaded = app.world.resource::<SimConfig>();
+        assert_eq!(reloaded.ant_speed, 120.0);
+        assert_eq!(reloaded.seed, overrides.seed);
+        assert_eq!(reloaded.map, overrides.map);
+        assert_eq!(reloaded.colonies, config.colonies);
+        assert_eq!(reloaded.food_sources, config.food_sources);
+        assert!(app.world.resource::<Events<ResetSimulation>>().is_empty());
+    }
+}
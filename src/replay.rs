@@ -0,0 +1,321 @@
+use bevy::{
+    log::warn,
+    prelude::{
+        Commands, Entity, Input, IntoSystemConfigs, KeyCode, Plugin, Quat, Query, Res, ResMut,
+        Resource, Time, Transform, Update, Vec3, With,
+    },
+};
+use bevy_egui::{
+    egui::{self, Slider},
+    EguiContexts, EguiPlugin,
+};
+
+use crate::{
+    ant::{Ant, CurrentTask},
+    colony::{Colony, ColonyId},
+    food::FoodSource,
+    grids::WorldGrid,
+    obstacle::Obstacles,
+    pheromone::Pheromones,
+    recording::Recording,
+    SimConfig,
+};
+
+/// 回放 [`Recording`]：用录下来的状态驱动蚂蚁、蚁巢、食物贴图和信息素图层，
+/// 不运行任何模拟逻辑；添加前要先插入 [`Recording`] 资源
+pub struct ReplayPlugin;
+
+/// 回放进度，可以暂停、拖动和调整速度
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayState {
+    pub playing: bool,
+    /// 播放速度，1 表示和模拟时间一样快
+    pub speed: f32,
+    /// 播放到的模拟时间
+    pub time: f64,
+    pub frame: usize,
+    shown: Option<usize>,
+    shown_pheromones: Option<usize>,
+    /// 当前显示的障碍物来自哪一帧，None 是文件头里的
+    shown_obstacles: Option<usize>,
+    ants: Vec<Entity>,
+    colonies: Vec<Entity>,
+    food: Vec<Entity>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        let recording = app
+            .world
+            .get_resource::<Recording>()
+            .expect("ReplayPlugin needs a Recording resource");
+        let config = recording.config.clone();
+        let obstacles = recording.obstacles.clone();
+        let time = if recording.is_empty() {
+            0.0
+        } else {
+            recording.elapsed(0)
+        };
+
+        app.insert_resource(Pheromones::new(&config))
+            .insert_resource(obstacles)
+            .insert_resource(config)
+            .insert_resource(ReplayState {
+                playing: true,
+                speed: 1.0,
+                time,
+                frame: 0,
+                shown: None,
+                shown_pheromones: None,
+                shown_obstacles: None,
+                ants: Vec::new(),
+                colonies: Vec::new(),
+                food: Vec::new(),
+            })
+            .add_systems(
+                Update,
+                (
+                    replay_controls,
+                    advance_replay,
+                    apply_replay_frame,
+                    apply_replay_obstacles,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn replay_controls(
+    mut contexts: EguiContexts,
+    mut state: ResMut<ReplayState>,
+    recording: Res<Recording>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if recording.is_empty() {
+        return;
+    }
+    let last = recording.len() - 1;
+
+    if !contexts.ctx_mut().wants_keyboard_input() {
+        if keys.just_pressed(KeyCode::Space) {
+            state.playing = !state.playing;
+        }
+        //方向键逐帧前后移动
+        let step =
+            keys.just_pressed(KeyCode::Right) as isize - keys.just_pressed(KeyCode::Left) as isize;
+        if step != 0 {
+            state.playing = false;
+            state.frame = state.frame.saturating_add_signed(step).min(last);
+            state.time = recording.elapsed(state.frame);
+        }
+    }
+
+    egui::Window::new("Replay").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            let label = if state.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                // 播完之后再点播放从头开始
+                if !state.playing && state.frame == last {
+                    state.frame = 0;
+                    state.time = recording.elapsed(0);
+                }
+                state.playing = !state.playing;
+            }
+            ui.label(format!(
+                "tick {} ({:.1}s)",
+                recording.tick(state.frame),
+                recording.elapsed(state.frame)
+            ));
+        });
+        let mut frame = state.frame;
+        if ui
+            .add(Slider::new(&mut frame, 0..=last).text("frame"))
+            .changed()
+        {
+            state.frame = frame;
+            state.time = recording.elapsed(frame);
+        }
+        ui.add(
+            Slider::new(&mut state.speed, 0.1..=100.0)
+                .logarithmic(true)
+                .text("speed"),
+        );
+        ui.label("space: play/pause, left/right: step");
+    });
+}
+
+fn advance_replay(mut state: ResMut<ReplayState>, recording: Res<Recording>, time: Res<Time>) {
+    if !state.playing || recording.is_empty() {
+        return;
+    }
+
+    state.time += time.delta_seconds_f64() * state.speed as f64;
+    state.frame = recording.frame_at(state.time);
+    if state.frame == recording.len() - 1 {
+        state.playing = false;
+    }
+}
+
+///只在帧号变化时更新；蚂蚁实体按顺序复用，数量不够时生成，多了就删掉
+#[allow(clippy::too_many_arguments)]
+fn apply_replay_frame(
+    mut commands: Commands,
+    mut state: ResMut<ReplayState>,
+    recording: Res<Recording>,
+    mut ant_query: Query<(&mut Transform, &mut CurrentTask, &mut ColonyId), With<Ant>>,
+    mut colony_query: Query<&mut Colony>,
+    mut food_query: Query<&mut FoodSource>,
+    mut pheromones: ResMut<Pheromones>,
+    config: Res<SimConfig>,
+) {
+    if recording.is_empty() || state.shown == Some(state.frame) {
+        return;
+    }
+    let frame = match recording.frame(state.frame) {
+        Ok(frame) => frame,
+        Err(e) => {
+            warn!("{e}");
+            state.playing = false;
+            return;
+        }
+    };
+    state.shown = Some(state.frame);
+
+    for (i, ant) in frame.ants.iter().enumerate() {
+        let rotation = Quat::from_rotation_z(ant.angle);
+        if let Some((mut transform, mut task, mut colony)) = state
+            .ants
+            .get(i)
+            .and_then(|entity| ant_query.get_mut(*entity).ok())
+        {
+            transform.translation.x = ant.pos.x;
+            transform.translation.y = ant.pos.y;
+            transform.rotation = rotation;
+            // 只在变化时写入，避免每帧都重新加载贴图
+            if task.0 != ant.task || *colony != ant.colony {
+                task.0 = ant.task;
+                *colony = ant.colony;
+            }
+            continue;
+        }
+
+        let entity = commands
+            .spawn((
+                Transform::from_xyz(ant.pos.x, ant.pos.y, config.ant_z_index)
+                    .with_rotation(rotation)
+                    .with_scale(Vec3::splat(config.ant_sprite_scale)),
+                Ant,
+                ant.colony,
+                CurrentTask(ant.task),
+            ))
+            .id();
+        if i < state.ants.len() {
+            state.ants[i] = entity;
+        } else {
+            state.ants.push(entity);
+        }
+    }
+    let num_ants = frame.ants.len();
+    for entity in state.ants.drain(num_ants..) {
+        commands.entity(entity).despawn();
+    }
+
+    // 贴图位置只在生成时设置，位置对不上（比如中间的食物搬完了）时重新生成
+    for (i, recorded) in frame.colonies.iter().enumerate() {
+        let existing = state.colonies.get(i).copied();
+        if let Some(mut colony) = existing.and_then(|entity| colony_query.get_mut(entity).ok()) {
+            if colony.pos == recorded.pos && colony.id == recorded.id {
+                colony.stockpile = recorded.stockpile;
+                colony.delivered = recorded.delivered;
+                continue;
+            }
+        }
+        if let Some(entity) = existing {
+            commands.entity(entity).despawn();
+        }
+
+        let mut colony = Colony::new(recorded.id, recorded.pos, recorded.radius);
+        colony.stockpile = recorded.stockpile;
+        colony.delivered = recorded.delivered;
+        let entity = commands.spawn(colony).id();
+        if i < state.colonies.len() {
+            state.colonies[i] = entity;
+        } else {
+            state.colonies.push(entity);
+        }
+    }
+    let num_colonies = frame.colonies.len();
+    for entity in state.colonies.drain(num_colonies..) {
+        commands.entity(entity).despawn();
+    }
+
+    for (i, recorded) in frame.food.iter().enumerate() {
+        let existing = state.food.get(i).copied();
+        if let Some(mut food) = existing.and_then(|entity| food_query.get_mut(entity).ok()) {
+            if food.pos == recorded.pos {
+                if food.amount != recorded.amount {
+                    food.amount = recorded.amount;
+                }
+                continue;
+            }
+        }
+        if let Some(entity) = existing {
+            commands.entity(entity).despawn();
+        }
+
+        let entity = commands.spawn(recorded.clone()).id();
+        if i < state.food.len() {
+            state.food[i] = entity;
+        } else {
+            state.food.push(entity);
+        }
+    }
+    let num_food = frame.food.len();
+    for entity in state.food.drain(num_food..) {
+        commands.entity(entity).despawn();
+    }
+
+    let pheromone_frame = recording.pheromone_frame(state.frame);
+    if pheromone_frame == state.shown_pheromones {
+        return;
+    }
+    state.shown_pheromones = pheromone_frame;
+    *pheromones = Pheromones::new(&config);
+    let layers = match pheromone_frame.map(|i| recording.pheromones(i)) {
+        Some(Ok(Some(layers))) => layers,
+        Some(Err(e)) => {
+            warn!("{e}");
+            return;
+        }
+        _ => return,
+    };
+    for (colony, (to_home, to_food)) in pheromones.colonies.iter_mut().zip(layers) {
+        colony.to_home =
+            WorldGrid::new(colony.to_home.color, to_home.into_iter().collect(), &config);
+        colony.to_food =
+            WorldGrid::new(colony.to_food.color, to_food.into_iter().collect(), &config);
+    }
+}
+
+/// 录制时编辑器改过墙的话，按当前帧换成那时的障碍物
+fn apply_replay_obstacles(
+    mut state: ResMut<ReplayState>,
+    recording: Res<Recording>,
+    mut obstacles: ResMut<Obstacles>,
+) {
+    if recording.is_empty() {
+        return;
+    }
+    let obstacle_frame = recording.obstacle_frame(state.frame);
+    if obstacle_frame == state.shown_obstacles {
+        return;
+    }
+    state.shown_obstacles = obstacle_frame;
+    match recording.obstacles_at(state.frame) {
+        Ok(recorded) => *obstacles = recorded,
+        Err(e) => warn!("{e}"),
+    }
+}
@@ -0,0 +1,514 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    math::vec2,
+    prelude::{
+        Entity, Event, Events, IntoSystemConfigs, Plugin, Transform, Update, Vec3, With, World,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ant::{
+        Acceleration, Age, Ant, AntDeaths, AntScanRadius, AntTask, CurrentTask, Energy, PhStrength,
+        TripStart, Velocity,
+    },
+    colony::{Colony, ColonyId},
+    control::{SimClock, SimTicks},
+    food::FoodSource,
+    grids::{DecayGrid, WorldGrid},
+    obstacle::Obstacles,
+    pathviz::PathVizGrid,
+    pheromone::{ColonyPheromones, Pheromones},
+    utils::SimRng,
+    SimConfig,
+};
+
+/// 快照格式版本，快照里的字段有不兼容的改动时加一
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// 整个模拟的状态：配置、所有蚂蚁、蚁巢、食物、信息素、路径图层、障碍物、
+/// 时钟和随机数状态，读回来之后可以接着跑出完全一样的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub config: SimConfig,
+    pub clock: SimClock,
+    pub rng: SimRng,
+    pub scan_radius: f32,
+    pub deaths: (u32, u32),
+    pub ants: Vec<AntState>,
+    pub colonies: Vec<ColonyState>,
+    pub food: Vec<FoodState>,
+    pub pheromones: Vec<PheromoneState>,
+    pub path_viz: Option<PathVizState>,
+    pub blocked: Vec<(i32, i32)>,
+    pub terrain: Vec<((i32, i32), f32)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AntState {
+    pub colony: usize,
+    pub translation: (f32, f32, f32),
+    pub velocity: (f32, f32),
+    pub acceleration: (f32, f32),
+    pub task: AntTask,
+    pub ph_strength: f32,
+    pub energy: f32,
+    pub age: f32,
+    pub lifespan: f32,
+    /// 旧快照里没有这一项，读取时按出生时出门算
+    #[serde(default)]
+    pub trip_start: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColonyState {
+    pub id: usize,
+    pub pos: (f32, f32),
+    pub radius: f32,
+    pub stockpile: f32,
+    pub delivered: u32,
+    pub births: u32,
+    pub delivery_rate: f32,
+    pub delivered_in_window: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoodState {
+    pub pos: (f32, f32),
+    pub radius: f32,
+    pub amount: f32,
+    pub initial_amount: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridState {
+    pub color: (u8, u8, u8),
+    pub signals: Vec<((i32, i32), f32)>,
+    pub steer_cache: Vec<((i32, i32), (f32, f32))>,
+    /// 衰减模型按沉积时间衰减时各格子的时间
+    #[serde(default)]
+    pub ages: Vec<((i32, i32), f32)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PheromoneState {
+    pub to_home: GridState,
+    pub to_food: GridState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathVizState {
+    pub home: Vec<((i32, i32), f32)>,
+    pub food: Vec<((i32, i32), f32)>,
+    #[serde(default)]
+    pub home_ages: Vec<((i32, i32), f32)>,
+    #[serde(default)]
+    pub food_ages: Vec<((i32, i32), f32)>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    Version { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "failed to access snapshot: {e}"),
+            SnapshotError::Parse(e) => write!(f, "failed to parse snapshot: {e}"),
+            SnapshotError::Serialize(e) => write!(f, "failed to write snapshot: {e}"),
+            SnapshotError::Version { found, expected } => write!(
+                f,
+                "snapshot version {found} is not supported (expected {expected})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// 存储的遍历顺序不固定，排序后写出的文件才稳定
+fn sorted_signals(cells: impl Iterator<Item = ((i32, i32), f32)>) -> Vec<((i32, i32), f32)> {
+    let mut signals: Vec<_> = cells.collect();
+    signals.sort_by_key(|(k, _)| *k);
+    signals
+}
+
+impl GridState {
+    fn capture(grid: &WorldGrid) -> Self {
+        Self {
+            color: grid.color,
+            signals: sorted_signals(grid.signals()),
+            steer_cache: grid
+                .steer_cache_entries()
+                .into_iter()
+                .map(|(k, v)| (k, v.into()))
+                .collect(),
+            ages: grid.signal_ages(),
+        }
+    }
+
+    fn restore(&self, config: &SimConfig) -> WorldGrid {
+        let mut grid = WorldGrid::new(self.color, self.signals.iter().copied().collect(), config);
+        grid.restore_steer_cache(self.steer_cache.iter().map(|&(k, (x, y))| (k, vec2(x, y))));
+        grid.restore_signal_ages(self.ages.iter().copied());
+        grid
+    }
+}
+
+impl Snapshot {
+    /// 从 World 里取出当前状态，蚂蚁等实体按查询顺序保存，恢复时顺序不变
+    pub fn capture(world: &mut World) -> Self {
+        let ants = world
+            .query_filtered::<(
+                &ColonyId,
+                &Transform,
+                &Velocity,
+                &Acceleration,
+                &CurrentTask,
+                &PhStrength,
+                &Energy,
+                &Age,
+                &TripStart,
+            ), With<Ant>>()
+            .iter(world)
+            .map(
+                |(
+                    colony,
+                    transform,
+                    velocity,
+                    acceleration,
+                    task,
+                    ph_strength,
+                    energy,
+                    age,
+                    trip,
+                )| {
+                    AntState {
+                        colony: colony.0,
+                        translation: transform.translation.into(),
+                        velocity: velocity.0.into(),
+                        acceleration: acceleration.0.into(),
+                        task: task.0,
+                        ph_strength: ph_strength.0,
+                        energy: energy.0,
+                        age: age.age,
+                        lifespan: age.lifespan,
+                        trip_start: trip.0,
+                    }
+                },
+            )
+            .collect();
+        let colonies = world
+            .query::<&Colony>()
+            .iter(world)
+            .map(|colony| ColonyState {
+                id: colony.id.0,
+                pos: colony.pos.into(),
+                radius: colony.radius,
+                stockpile: colony.stockpile,
+                delivered: colony.delivered,
+                births: colony.births,
+                delivery_rate: colony.delivery_rate,
+                delivered_in_window: colony.delivered_in_window,
+            })
+            .collect();
+        let food = world
+            .query::<&FoodSource>()
+            .iter(world)
+            .map(|food| FoodState {
+                pos: food.pos.into(),
+                radius: food.radius,
+                amount: food.amount,
+                initial_amount: food.initial_amount,
+            })
+            .collect();
+
+        let pheromones = world
+            .resource::<Pheromones>()
+            .colonies
+            .iter()
+            .map(|colony| PheromoneState {
+                to_home: GridState::capture(&colony.to_home),
+                to_food: GridState::capture(&colony.to_food),
+            })
+            .collect();
+        let path_viz = world.get_resource::<PathVizGrid>().map(|viz| PathVizState {
+            home: sorted_signals(viz.dg_home.cells()),
+            food: sorted_signals(viz.dg_food.cells()),
+            home_ages: viz.dg_home.ages(),
+            food_ages: viz.dg_food.ages(),
+        });
+        let obstacles = world.resource::<Obstacles>();
+        let mut blocked: Vec<_> = obstacles.cells().copied().collect();
+        blocked.sort();
+        let mut terrain: Vec<_> = obstacles.terrain_cells().map(|(k, v)| (*k, *v)).collect();
+        terrain.sort_by_key(|(k, _)| *k);
+        let deaths = world.resource::<AntDeaths>();
+
+        Self {
+            version: SNAPSHOT_VERSION,
+            config: world.resource::<SimConfig>().clone(),
+            clock: world.resource::<SimClock>().clone(),
+            rng: world.resource::<SimRng>().clone(),
+            scan_radius: world.resource::<AntScanRadius>().0,
+            deaths: (deaths.starved, deaths.old_age),
+            ants,
+            colonies,
+            food,
+            pheromones,
+            path_viz,
+            blocked,
+            terrain,
+        }
+    }
+
+    /// 用快照替换 World 里的模拟状态，不会触发 [`crate::ResetSimulation`]
+    pub fn restore(&self, world: &mut World) {
+        let config = &self.config;
+
+        let mut entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<Ant>>()
+            .iter(world)
+            .collect();
+        entities.extend(world.query_filtered::<Entity, With<Colony>>().iter(world));
+        entities.extend(
+            world
+                .query_filtered::<Entity, With<FoodSource>>()
+                .iter(world),
+        );
+        for entity in entities {
+            world.despawn(entity);
+        }
+
+        for ant in self.ants.iter() {
+            world.spawn((
+                Transform::from_translation(Vec3::from(ant.translation))
+                    .with_scale(Vec3::splat(config.ant_sprite_scale)),
+                Ant,
+                ColonyId(ant.colony),
+                CurrentTask(ant.task),
+                Velocity(vec2(ant.velocity.0, ant.velocity.1)),
+                Acceleration(vec2(ant.acceleration.0, ant.acceleration.1)),
+                PhStrength(ant.ph_strength),
+                Energy(ant.energy),
+                Age {
+                    age: ant.age,
+                    lifespan: ant.lifespan,
+                },
+                TripStart(ant.trip_start),
+            ));
+        }
+        for colony in self.colonies.iter() {
+            let mut restored = Colony::new(
+                ColonyId(colony.id),
+                vec2(colony.pos.0, colony.pos.1),
+                colony.radius,
+            );
+            restored.stockpile = colony.stockpile;
+            restored.delivered = colony.delivered;
+            restored.births = colony.births;
+            restored.delivery_rate = colony.delivery_rate;
+            restored.delivered_in_window = colony.delivered_in_window;
+            world.spawn(restored);
+        }
+        for food in self.food.iter() {
+            world.spawn(FoodSource {
+                pos: vec2(food.pos.0, food.pos.1),
+                radius: food.radius,
+                amount: food.amount,
+                initial_amount: food.initial_amount,
+            });
+        }
+
+        world.insert_resource(Pheromones {
+            colonies: self
+                .pheromones
+                .iter()
+                .map(|colony| ColonyPheromones {
+                    to_home: colony.to_home.restore(config),
+                    to_food: colony.to_food.restore(config),
+                })
+                .collect(),
+        });
+        if let (Some(state), true) = (&self.path_viz, world.contains_resource::<PathVizGrid>()) {
+            let max = config.viz_max_color_strength;
+            let mut dg_home = DecayGrid::new(
+                state.home.iter().copied().collect(),
+                max,
+                config.viz_decay(),
+            );
+            let mut dg_food = DecayGrid::new(
+                state.food.iter().copied().collect(),
+                max,
+                config.viz_decay(),
+            );
+            dg_home.restore_ages(state.home_ages.iter().copied());
+            dg_food.restore_ages(state.food_ages.iter().copied());
+            world.insert_resource(PathVizGrid { dg_home, dg_food });
+        }
+        world.insert_resource(Obstacles::from_cells(
+            config,
+            self.blocked.iter().copied(),
+            self.terrain.iter().copied(),
+        ));
+        world.insert_resource(AntScanRadius(self.scan_radius));
+        world.insert_resource(AntDeaths {
+            starved: self.deaths.0,
+            old_age: self.deaths.1,
+        });
+        world.insert_resource(self.rng.clone());
+        world.insert_resource(self.clock.clone());
+        world.insert_resource(config.clone());
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let text = ron::to_string(self).map_err(SnapshotError::Serialize)?;
+        fs::write(path, text).map_err(SnapshotError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let text = fs::read_to_string(path).map_err(SnapshotError::Io)?;
+        // 先只读版本号，版本不对时给出明确的错误而不是字段解析失败
+        #[derive(Deserialize)]
+        struct Header {
+            version: u32,
+        }
+        let header: Header = ron::from_str(&text).map_err(SnapshotError::Parse)?;
+        if header.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version {
+                found: header.version,
+                expected: SNAPSHOT_VERSION,
+            });
+        }
+        ron::from_str(&text).map_err(SnapshotError::Parse)
+    }
+}
+
+/// 请求把当前状态保存到文件
+#[derive(Event, Debug, Clone)]
+pub struct SaveSnapshot(pub PathBuf);
+
+/// 请求从文件恢复状态，在这一帧的模拟步之前生效
+#[derive(Event, Debug, Clone)]
+pub struct LoadSnapshot(pub PathBuf);
+
+/// 处理 [`SaveSnapshot`] 和 [`LoadSnapshot`] 事件，出错时只打印警告
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<SaveSnapshot>()
+            .add_event::<LoadSnapshot>()
+            .add_systems(Update, handle_snapshot_events.before(SimTicks));
+    }
+}
+
+fn handle_snapshot_events(world: &mut World) {
+    let saves: Vec<_> = world
+        .resource_mut::<Events<SaveSnapshot>>()
+        .drain()
+        .collect();
+    for SaveSnapshot(path) in saves {
+        match Snapshot::capture(world).save(&path) {
+            Ok(()) => bevy::log::info!("saved snapshot to {}", path.display()),
+            Err(e) => bevy::log::warn!("{e}"),
+        }
+    }
+
+    let loads: Vec<_> = world
+        .resource_mut::<Events<LoadSnapshot>>()
+        .drain()
+        .collect();
+    for LoadSnapshot(path) in loads {
+        match Snapshot::load(&path) {
+            Ok(snapshot) => {
+                snapshot.restore(world);
+                bevy::log::info!("loaded snapshot from {}", path.display());
+            }
+            Err(e) => bevy::log::warn!("{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless, FoodSpec, GridBackend};
+
+    /// 跑 300 步存快照、读回来再跑 300 步，要和一口气跑 600 步完全一样
+    fn assert_continues(name: &str, config: SimConfig) {
+        let path =
+            std::env::temp_dir().join(format!("ants-snapshot-{name}-{}.ron", std::process::id()));
+
+        let mut app = headless::run_headless(config.clone(), 300);
+        Snapshot::capture(&mut app.world).save(&path).unwrap();
+        let mut app = headless::headless_app(config.clone());
+        app.world.send_event(LoadSnapshot(path.clone()));
+        let mut resumed = headless::run_app(app, 300);
+        fs::remove_file(&path).ok();
+
+        let mut uninterrupted = headless::run_headless(config, 600);
+        let resumed = Snapshot::capture(&mut resumed.world);
+        let uninterrupted = Snapshot::capture(&mut uninterrupted.world);
+        assert_eq!(resumed.clock.tick, 600, "{name}");
+        assert!(
+            uninterrupted.colonies.iter().any(|c| c.delivered > 0),
+            "{name}"
+        );
+        assert_eq!(
+            ron::to_string(&resumed).unwrap(),
+            ron::to_string(&uninterrupted).unwrap(),
+            "{name}"
+        );
+    }
+
+    fn config(backend: GridBackend) -> SimConfig {
+        SimConfig {
+            seed: Some(7),
+            num_ants: 300,
+            ph_grid_backend: backend,
+            // 食物放近一点，600 步里就有蚂蚁搬回家
+            food_sources: vec![FoodSpec {
+                location: (600.0, -250.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn snapshot_continues_sparse() {
+        assert_continues("sparse", config(GridBackend::Sparse));
+    }
+
+    #[test]
+    fn snapshot_continues_dense() {
+        assert_continues("dense", config(GridBackend::Dense));
+    }
+
+    #[test]
+    fn snapshot_continues_chunked() {
+        assert_continues("chunked", config(GridBackend::Chunked));
+    }
+
+    #[test]
+    fn snapshot_continues_with_diffusion() {
+        assert_continues(
+            "diffusion",
+            SimConfig {
+                ph_diffusion_rate: 0.1,
+                ph_diffusion_loss: 0.05,
+                ..config(GridBackend::Sparse)
+            },
+        );
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+
+use crate::GridBackend;
+
+/// 已加载的块，见 [`SignalStore::chunks`]
+pub type Chunks<'a> = Box<dyn Iterator<Item = ((i32, i32), &'a [f32])> + 'a>;
+
+/// 网格信号的存储方式，key 是格子坐标；[`crate::grids::DecayGrid`] 的衰减、扩散都只通过这些接口读写
+pub trait SignalStore: Send + Sync {
+    /// 格子上的信号，没有时为 None
+    fn get(&self, key: &(i32, i32)) -> Option<f32>;
+
+    /// 已有信号的格子的可写引用，没有时为 None
+    fn get_mut(&mut self, key: &(i32, i32)) -> Option<&mut f32>;
+
+    fn insert(&mut self, key: (i32, i32), value: f32);
+
+    fn remove(&mut self, key: &(i32, i32));
+
+    fn clear(&mut self);
+
+    /// 有信号的格子数（稀疏存储里衰减到 0 但还没清理的也算）
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 所有有信号的格子
+    fn cells(&self) -> Box<dyn Iterator<Item = ((i32, i32), f32)> + '_>;
+
+    /// 依次修改每个有信号的格子
+    fn for_each_mut(&mut self, f: &mut dyn FnMut((i32, i32), &mut f32));
+
+    /// 只保留 f 返回 true 的格子
+    fn retain(&mut self, f: &mut dyn FnMut(&(i32, i32), f32) -> bool);
+
+    /// center 周围 radius 个格子以内有信号的格子，顺序不定
+    fn cells_in_radius(&self, center: (i32, i32), radius: f32) -> Vec<((i32, i32), f32)>;
+
+    /// 区域查询时要查看的索引单元数：稀疏存储的桶数、分块存储的已加载块数；稠密存储按坐标直接访问，为 0
+    fn index_size(&self) -> usize {
+        0
+    }
+
+    /// 分块存储的已加载块：块坐标和按行排列的 CHUNK_SIZE² 个值（0 为没有信号），渲染按块进行；
+    /// 其他存储返回 None
+    fn chunks(&self) -> Option<Chunks<'_>> {
+        None
+    }
+}
+
+impl GridBackend {
+    /// min..=max 是格子坐标的范围，只有稠密存储用到，范围外的写入会被丢掉
+    pub fn store(&self, min: (i32, i32), max: (i32, i32)) -> Box<dyn SignalStore> {
+        match self {
+            GridBackend::Sparse => Box::<SparseStore>::default(),
+            GridBackend::Dense => Box::new(DenseStore::new(min, max)),
+            GridBackend::Chunked => Box::<ChunkedStore>::default(),
+        }
+    }
+}
+
+/// 稀疏存储空间索引每个桶的边长（格子数）
+const BUCKET_SIZE: i32 = 8;
+
+fn bucket_of(key: &(i32, i32)) -> (i32, i32) {
+    (key.0.div_euclid(BUCKET_SIZE), key.1.div_euclid(BUCKET_SIZE))
+}
+
+/// HashMap，只存有信号的格子，适合很大但信号稀疏的世界；
+/// 另有一个按桶划分的空间索引，增删格子时同步更新，区域查询只看相交的桶
+#[derive(Debug, Default)]
+pub struct SparseStore {
+    values: HashMap<(i32, i32), f32>,
+    buckets: HashMap<(i32, i32), Vec<(i32, i32)>>,
+}
+
+impl From<HashMap<(i32, i32), f32>> for SparseStore {
+    fn from(values: HashMap<(i32, i32), f32>) -> Self {
+        let mut buckets: HashMap<_, Vec<_>> = HashMap::new();
+        for key in values.keys() {
+            buckets.entry(bucket_of(key)).or_default().push(*key);
+        }
+        Self { values, buckets }
+    }
+}
+
+impl SparseStore {
+    fn unindex(&mut self, key: &(i32, i32)) {
+        let bucket = bucket_of(key);
+        let Some(keys) = self.buckets.get_mut(&bucket) else {
+            return;
+        };
+        if let Some(i) = keys.iter().position(|k| k == key) {
+            keys.swap_remove(i);
+        }
+        if keys.is_empty() {
+            self.buckets.remove(&bucket);
+        }
+    }
+}
+
+/// 衰减到 0 还没清理的格子和其他存储一样当作没有信号
+impl SignalStore for SparseStore {
+    fn get(&self, key: &(i32, i32)) -> Option<f32> {
+        self.values.get(key).copied().filter(|v| *v > 0.0)
+    }
+
+    fn get_mut(&mut self, key: &(i32, i32)) -> Option<&mut f32> {
+        self.values.get_mut(key).filter(|v| **v > 0.0)
+    }
+
+    fn insert(&mut self, key: (i32, i32), value: f32) {
+        if self.values.insert(key, value).is_none() {
+            self.buckets.entry(bucket_of(&key)).or_default().push(key);
+        }
+    }
+
+    fn remove(&mut self, key: &(i32, i32)) {
+        if self.values.remove(key).is_some() {
+            self.unindex(key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.buckets.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = ((i32, i32), f32)> + '_> {
+        Box::new(
+            self.values
+                .iter()
+                .filter(|(_, v)| **v > 0.0)
+                .map(|(k, v)| (*k, *v)),
+        )
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut((i32, i32), &mut f32)) {
+        for (key, value) in self.values.iter_mut() {
+            if *value > 0.0 {
+                f(*key, value);
+            }
+        }
+    }
+
+    fn retain(&mut self, f: &mut dyn FnMut(&(i32, i32), f32) -> bool) {
+        let mut dropped = Vec::new();
+        self.values.retain(|key, value| {
+            let keep = *value > 0.0 && f(key, *value);
+            if !keep {
+                dropped.push(*key);
+            }
+            keep
+        });
+        for key in dropped.iter() {
+            self.unindex(key);
+        }
+    }
+
+    fn cells_in_radius(&self, center: (i32, i32), radius: f32) -> Vec<((i32, i32), f32)> {
+        let r = radius.max(0.0) as i32;
+        let r2 = radius * radius;
+        let min = bucket_of(&(center.0 - r, center.1 - r));
+        let max = bucket_of(&(center.0 + r, center.1 + r));
+        let mut found = Vec::new();
+        for by in min.1..=max.1 {
+            for bx in min.0..=max.0 {
+                let Some(keys) = self.buckets.get(&(bx, by)) else {
+                    continue;
+                };
+                for key in keys.iter() {
+                    let (dx, dy) = (key.0 - center.0, key.1 - center.1);
+                    if (dx * dx + dy * dy) as f32 > r2 {
+                        continue;
+                    }
+                    match self.values.get(key) {
+                        Some(&value) if value > 0.0 => found.push((*key, value)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn index_size(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+/// 覆盖整个世界的连续数组，查找 O(1)，区域查询直接扫描方框；
+/// 0 表示没有信号
+#[derive(Debug)]
+pub struct DenseStore {
+    min: (i32, i32),
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    /// 大于 0 的格子数
+    live: usize,
+}
+
+impl DenseStore {
+    pub fn new(min: (i32, i32), max: (i32, i32)) -> Self {
+        let width = (max.0 - min.0 + 1).max(0) as usize;
+        let height = (max.1 - min.1 + 1).max(0) as usize;
+        Self {
+            min,
+            width,
+            height,
+            values: vec![0.0; width * height],
+            live: 0,
+        }
+    }
+
+    fn index(&self, key: &(i32, i32)) -> Option<usize> {
+        let (x, y) = (key.0 - self.min.0, key.1 - self.min.1);
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    fn key(&self, index: usize) -> (i32, i32) {
+        (
+            (index % self.width) as i32 + self.min.0,
+            (index / self.width) as i32 + self.min.1,
+        )
+    }
+
+    fn recount(&mut self) {
+        self.live = self.values.iter().filter(|v| **v > 0.0).count();
+    }
+}
+
+impl SignalStore for DenseStore {
+    fn get(&self, key: &(i32, i32)) -> Option<f32> {
+        let value = self.values[self.index(key)?];
+        (value > 0.0).then_some(value)
+    }
+
+    fn get_mut(&mut self, key: &(i32, i32)) -> Option<&mut f32> {
+        let index = self.index(key)?;
+        let value = &mut self.values[index];
+        (*value > 0.0).then_some(value)
+    }
+
+    fn insert(&mut self, key: (i32, i32), value: f32) {
+        let Some(index) = self.index(&key) else {
+            return;
+        };
+        let old = std::mem::replace(&mut self.values[index], value.max(0.0));
+        match (old > 0.0, value > 0.0) {
+            (false, true) => self.live += 1,
+            (true, false) => self.live -= 1,
+            _ => {}
+        }
+    }
+
+    fn remove(&mut self, key: &(i32, i32)) {
+        self.insert(*key, 0.0);
+    }
+
+    fn clear(&mut self) {
+        self.values.fill(0.0);
+        self.live = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.live
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = ((i32, i32), f32)> + '_> {
+        Box::new(
+            self.values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v > 0.0)
+                .map(|(i, v)| (self.key(i), *v)),
+        )
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut((i32, i32), &mut f32)) {
+        let (width, min) = (self.width, self.min);
+        for (i, value) in self.values.iter_mut().enumerate() {
+            if *value > 0.0 {
+                let key = ((i % width) as i32 + min.0, (i / width) as i32 + min.1);
+                f(key, value);
+            }
+        }
+        self.recount();
+    }
+
+    fn retain(&mut self, f: &mut dyn FnMut(&(i32, i32), f32) -> bool) {
+        let (width, min) = (self.width, self.min);
+        for (i, value) in self.values.iter_mut().enumerate() {
+            let key = ((i % width) as i32 + min.0, (i / width) as i32 + min.1);
+            if *value > 0.0 && !f(&key, *value) {
+                *value = 0.0;
+            }
+        }
+        self.recount();
+    }
+
+    fn cells_in_radius(&self, center: (i32, i32), radius: f32) -> Vec<((i32, i32), f32)> {
+        let r = radius.max(0.0) as i32;
+        let r2 = radius * radius;
+        let mut found = Vec::new();
+        for dy in -r..=r {
+            for dx in -r..=r {
+                if (dx * dx + dy * dy) as f32 > r2 {
+                    continue;
+                }
+                let key = (center.0 + dx, center.1 + dy);
+                if let Some(value) = self.get(&key) {
+                    found.push((key, value));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// 分块存储每块的边长（格子数）
+pub const CHUNK_SIZE: i32 = 64;
+
+const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// 一块格子，0 表示没有信号
+#[derive(Debug)]
+struct Chunk {
+    values: Box<[f32]>,
+    /// 大于 0 的格子数，为 0 时整块释放
+    live: usize,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            values: vec![0.0; CHUNK_CELLS].into_boxed_slice(),
+            live: 0,
+        }
+    }
+
+    fn recount(&mut self) {
+        self.live = self.values.iter().filter(|v| **v > 0.0).count();
+    }
+}
+
+/// 按 [`CHUNK_SIZE`] 分块的稀疏存储：块在第一次写入时分配，信号全部消失后释放，
+/// 世界可以没有边界；衰减、区域查询和渲染都只处理已加载的块
+#[derive(Debug, Default)]
+pub struct ChunkedStore {
+    chunks: HashMap<(i32, i32), Chunk>,
+    /// 所有块里大于 0 的格子数
+    live: usize,
+}
+
+/// 格子所在的块坐标和块内下标
+fn split(key: &(i32, i32)) -> ((i32, i32), usize) {
+    let chunk = (key.0.div_euclid(CHUNK_SIZE), key.1.div_euclid(CHUNK_SIZE));
+    let (x, y) = (key.0.rem_euclid(CHUNK_SIZE), key.1.rem_euclid(CHUNK_SIZE));
+    (chunk, (y * CHUNK_SIZE + x) as usize)
+}
+
+fn join(chunk: (i32, i32), index: usize) -> (i32, i32) {
+    let index = index as i32;
+    (
+        chunk.0 * CHUNK_SIZE + index % CHUNK_SIZE,
+        chunk.1 * CHUNK_SIZE + index / CHUNK_SIZE,
+    )
+}
+
+impl ChunkedStore {
+    /// 逐块修改后重新计数，释放空块
+    fn recount(&mut self) {
+        self.chunks.retain(|_, chunk| chunk.live > 0);
+        self.live = self.chunks.values().map(|chunk| chunk.live).sum();
+    }
+}
+
+impl SignalStore for ChunkedStore {
+    fn get(&self, key: &(i32, i32)) -> Option<f32> {
+        let (chunk, index) = split(key);
+        let value = self.chunks.get(&chunk)?.values[index];
+        (value > 0.0).then_some(value)
+    }
+
+    fn get_mut(&mut self, key: &(i32, i32)) -> Option<&mut f32> {
+        let (chunk, index) = split(key);
+        let value = &mut self.chunks.get_mut(&chunk)?.values[index];
+        (*value > 0.0).then_some(value)
+    }
+
+    fn insert(&mut self, key: (i32, i32), value: f32) {
+        if value <= 0.0 {
+            self.remove(&key);
+            return;
+        }
+        let (chunk, index) = split(&key);
+        let chunk = self.chunks.entry(chunk).or_insert_with(Chunk::new);
+        if chunk.values[index] <= 0.0 {
+            chunk.live += 1;
+            self.live += 1;
+        }
+        chunk.values[index] = value;
+    }
+
+    fn remove(&mut self, key: &(i32, i32)) {
+        let (chunk_key, index) = split(key);
+        let Some(chunk) = self.chunks.get_mut(&chunk_key) else {
+            return;
+        };
+        if chunk.values[index] > 0.0 {
+            chunk.values[index] = 0.0;
+            chunk.live -= 1;
+            self.live -= 1;
+        }
+        if chunk.live == 0 {
+            self.chunks.remove(&chunk_key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.chunks.clear();
+        self.live = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.live
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = ((i32, i32), f32)> + '_> {
+        Box::new(self.chunks.iter().flat_map(|(&chunk_key, chunk)| {
+            chunk
+                .values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| **v > 0.0)
+                .map(move |(i, v)| (join(chunk_key, i), *v))
+        }))
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut((i32, i32), &mut f32)) {
+        for (&chunk_key, chunk) in self.chunks.iter_mut() {
+            for (i, value) in chunk.values.iter_mut().enumerate() {
+                if *value > 0.0 {
+                    f(join(chunk_key, i), value);
+                }
+            }
+            chunk.recount();
+        }
+        self.recount();
+    }
+
+    fn retain(&mut self, f: &mut dyn FnMut(&(i32, i32), f32) -> bool) {
+        for (&chunk_key, chunk) in self.chunks.iter_mut() {
+            for (i, value) in chunk.values.iter_mut().enumerate() {
+                if *value > 0.0 && !f(&join(chunk_key, i), *value) {
+                    *value = 0.0;
+                }
+            }
+            chunk.recount();
+        }
+        self.recount();
+    }
+
+    /// 只扫描和方框相交的已加载块
+    fn cells_in_radius(&self, center: (i32, i32), radius: f32) -> Vec<((i32, i32), f32)> {
+        let r = radius.max(0.0) as i32;
+        let r2 = radius * radius;
+        let (min, max) = ((center.0 - r, center.1 - r), (center.0 + r, center.1 + r));
+        let (min_chunk, _) = split(&min);
+        let (max_chunk, _) = split(&max);
+        let mut found = Vec::new();
+        for cy in min_chunk.1..=max_chunk.1 {
+            for cx in min_chunk.0..=max_chunk.0 {
+                let Some(chunk) = self.chunks.get(&(cx, cy)) else {
+                    continue;
+                };
+                let (x0, y0) = (cx * CHUNK_SIZE, cy * CHUNK_SIZE);
+                for y in min.1.max(y0)..=max.1.min(y0 + CHUNK_SIZE - 1) {
+                    for x in min.0.max(x0)..=max.0.min(x0 + CHUNK_SIZE - 1) {
+                        let (dx, dy) = (x - center.0, y - center.1);
+                        if (dx * dx + dy * dy) as f32 > r2 {
+                            continue;
+                        }
+                        let value = chunk.values[((y - y0) * CHUNK_SIZE + x - x0) as usize];
+                        if value > 0.0 {
+                            found.push(((x, y), value));
+                        }
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn index_size(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn chunks(&self) -> Option<Chunks<'_>> {
+        Some(Box::new(
+            self.chunks
+                .iter()
+                .map(|(&chunk_key, chunk)| (chunk_key, &chunk.values[..])),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const MIN: (i32, i32) = (-150, -150);
+    const MAX: (i32, i32) = (150, 150);
+
+    fn stores() -> Vec<Box<dyn SignalStore>> {
+        [
+            GridBackend::Sparse,
+            GridBackend::Dense,
+            GridBackend::Chunked,
+        ]
+        .iter()
+        .map(|backend| backend.store(MIN, MAX))
+        .collect()
+    }
+
+    fn sorted(mut cells: Vec<((i32, i32), f32)>) -> Vec<((i32, i32), f32)> {
+        cells.sort_by_key(|(key, _)| *key);
+        cells
+    }
+
+    /// 三种存储的内容、数量和范围查询结果都一样
+    fn assert_same(stores: &[Box<dyn SignalStore>]) {
+        for store in stores.iter() {
+            assert_eq!(store.len(), stores[0].cells().count());
+        }
+        assert_same_cells(stores);
+    }
+
+    /// 同上，但不比较 len()：稀疏存储里衰减到 0 的格子清理前还算在 len() 里
+    fn assert_same_cells(stores: &[Box<dyn SignalStore>]) {
+        let expected = sorted(stores[0].cells().collect());
+        let centers = [(0, 0), (-1, -1), (63, 64), (-64, -65), (7, -8), (120, -130)];
+        for store in stores.iter() {
+            assert_eq!(sorted(store.cells().collect()), expected);
+            for (key, value) in expected.iter() {
+                assert_eq!(store.get(key), Some(*value));
+            }
+            for center in centers {
+                for radius in [0.0, 1.5, 9.0, 70.0] {
+                    assert_eq!(
+                        sorted(store.cells_in_radius(center, radius)),
+                        sorted(stores[0].cells_in_radius(center, radius)),
+                        "{center:?} {radius}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stores_agree() {
+        let mut stores = stores();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        // 块和桶的边界两侧，以及负数坐标
+        let edges = [
+            -129, -128, -65, -64, -63, -9, -8, -1, 0, 7, 8, 63, 64, 127, 128,
+        ];
+        let mut keys: Vec<_> = edges
+            .iter()
+            .flat_map(|&x| edges.iter().map(move |&y| (x, y)))
+            .collect();
+        keys.extend((0..500).map(|_| (rng.gen_range(-140..=140), rng.gen_range(-140..=140))));
+
+        for key in keys.iter() {
+            let value = rng.gen_range(1.0..100.0);
+            stores
+                .iter_mut()
+                .for_each(|store| store.insert(*key, value));
+        }
+        assert_same(&stores);
+
+        for key in keys.iter().step_by(3) {
+            stores.iter_mut().for_each(|store| store.remove(key));
+        }
+        // 删掉不存在的格子不影响计数
+        stores.iter_mut().for_each(|store| store.remove(&(1, 1)));
+        stores.iter_mut().for_each(|store| store.remove(&(1, 1)));
+        assert_same(&stores);
+
+        for store in stores.iter_mut() {
+            store.retain(&mut |key, value| value > 30.0 || key.0 < 0);
+        }
+        assert_same(&stores);
+
+        for key in keys.iter().step_by(5) {
+            stores.iter_mut().for_each(|store| store.insert(*key, 42.0));
+        }
+        assert_same(&stores);
+
+        // 一部分格子衰减到 0，清理之前再沉积一次，和 DecayGrid::add_value 一样先 get_mut
+        for store in stores.iter_mut() {
+            store.for_each_mut(&mut |key, v| {
+                if (key.0 + key.1).rem_euclid(3) == 0 {
+                    *v = 0.0;
+                }
+            });
+        }
+        for key in keys.iter() {
+            if (key.0 + key.1).rem_euclid(3) == 0 {
+                stores
+                    .iter()
+                    .for_each(|store| assert_eq!(store.get(key), None));
+            }
+        }
+        assert_same_cells(&stores);
+        for key in keys.iter().step_by(2) {
+            for store in stores.iter_mut() {
+                match store.get_mut(key) {
+                    Some(v) => *v += 10.0,
+                    None => store.insert(*key, 40.0),
+                }
+            }
+        }
+        assert_same_cells(&stores);
+        for store in stores.iter_mut() {
+            store.retain(&mut |_, v| v > 0.0);
+        }
+        assert_same(&stores);
+
+        for store in stores.iter_mut() {
+            store.retain(&mut |_, _| false);
+            assert!(store.is_empty());
+            assert_eq!(store.index_size(), 0);
+        }
+    }
+}
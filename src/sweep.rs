@@ -0,0 +1,313 @@
+use std::{
+    fmt, fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use bevy::{
+    ecs::schedule::ExecutorKind,
+    prelude::{App, Update, With},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    ant::Ant,
+    colony::Colony,
+    control::SimUpdate,
+    headless::{headless_app, run_app},
+    metrics::{MetricsHistory, MetricsPlugin, MetricsSample},
+    ConfigError, SimConfig,
+};
+
+/// 稳定阶段取运行的最后这一部分
+const STEADY_STATE_FRACTION: f64 = 0.25;
+
+/// 参数扫描定义，从 RON 文件读取，示例见 `sweeps/`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SweepSpec {
+    /// 基础配置文件，不写时用默认参数
+    #[serde(default)]
+    pub config: Option<String>,
+    pub ticks: u64,
+    /// 每组参数跑几个种子，种子依次是 first_seed, first_seed + 1, ...
+    #[serde(default = "default_seeds")]
+    pub seeds: u64,
+    #[serde(default)]
+    pub first_seed: u64,
+    /// 统计间隔（模拟秒）
+    #[serde(default = "default_metrics_interval")]
+    pub metrics_interval: f32,
+    pub params: Vec<SweepParam>,
+}
+
+fn default_seeds() -> u64 {
+    1
+}
+
+fn default_metrics_interval() -> f32 {
+    1.0
+}
+
+/// 一个扫描参数，name 是 [`SimConfig`] 的字段名（不区分大小写）
+#[derive(Debug, Clone, Deserialize)]
+pub struct SweepParam {
+    pub name: String,
+    pub values: SweepValues,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SweepValues {
+    /// 从 from 到 to（包含两端）均匀取 steps 个值
+    Range {
+        from: f64,
+        to: f64,
+        steps: usize,
+    },
+    List(Vec<f64>),
+}
+
+impl SweepValues {
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            SweepValues::Range { from, to, steps } => match steps {
+                0 => Vec::new(),
+                1 => vec![*from],
+                _ => (0..*steps)
+                    .map(|i| from + (to - from) * i as f64 / (*steps - 1) as f64)
+                    .collect(),
+            },
+            SweepValues::List(values) => values.clone(),
+        }
+    }
+}
+
+/// 扫描里的一次运行：一组参数加一个种子
+#[derive(Debug, Clone)]
+pub struct SweepRun {
+    /// (参数名, 值)，顺序和定义里一样
+    pub params: Vec<(String, f64)>,
+    pub seed: u64,
+    pub config: SimConfig,
+}
+
+/// 一次运行的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// 第一次送达食物的模拟时间（秒），一直没送达时为 None
+    pub first_delivery: Option<f64>,
+    /// 稳定阶段（最后 1/4）每分钟送达的食物
+    pub throughput: f64,
+    /// 稳定阶段平均往返用时（秒）
+    pub round_trip: Option<f64>,
+    /// 由往返用时和蚂蚁速度估算的单程路径长度（世界坐标），绕路越少越短
+    pub trail_length: Option<f64>,
+    pub delivered: u32,
+    pub ants: usize,
+}
+
+impl SweepSpec {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SweepError> {
+        let text = fs::read_to_string(path).map_err(SweepError::Io)?;
+        ron::from_str(&text).map_err(SweepError::Parse)
+    }
+
+    /// 展开成所有参数组合乘以所有种子，每组配置都先校验
+    pub fn runs(&self) -> Result<Vec<SweepRun>, SweepError> {
+        let base = match &self.config {
+            Some(path) => SimConfig::load(path).map_err(SweepError::Config)?,
+            None => SimConfig::default(),
+        };
+
+        let mut combos: Vec<Vec<(String, f64)>> = vec![Vec::new()];
+        for param in self.params.iter() {
+            let values = param.values.values();
+            if values.is_empty() {
+                return Err(SweepError::Invalid(format!(
+                    "`{}` has no values",
+                    param.name
+                )));
+            }
+            combos = combos
+                .into_iter()
+                .flat_map(|combo| {
+                    values.iter().map(move |value| {
+                        let mut combo = combo.clone();
+                        combo.push((param.name.to_lowercase(), *value));
+                        combo
+                    })
+                })
+                .collect();
+        }
+
+        let mut runs = Vec::new();
+        for params in combos {
+            let mut config = base.clone();
+            for (name, value) in params.iter() {
+                config = set_param(&config, name, *value)?;
+            }
+            config.validate().map_err(SweepError::Config)?;
+
+            for seed in self.first_seed..self.first_seed + self.seeds {
+                let mut config = config.clone();
+                config.seed = Some(seed);
+                runs.push(SweepRun {
+                    params: params.clone(),
+                    seed,
+                    config,
+                });
+            }
+        }
+        Ok(runs)
+    }
+}
+
+/// 按字段名修改配置里的一个数值参数，整数字段只接受整数
+pub fn set_param(config: &SimConfig, name: &str, value: f64) -> Result<SimConfig, SweepError> {
+    let invalid = |reason: &str| SweepError::Invalid(format!("`{name}` {reason}"));
+
+    let mut fields = serde_json::to_value(config).map_err(|e| invalid(&e.to_string()))?;
+    let field = fields
+        .get_mut(name)
+        .ok_or_else(|| invalid("is not a config field"))?;
+    *field = match field {
+        Value::Number(n) if n.is_f64() => Value::from(value),
+        Value::Number(_) if value.fract() == 0.0 && value >= 0.0 => Value::from(value as u64),
+        Value::Number(_) => return Err(invalid(&format!("must be a whole number, got {value}"))),
+        Value::Bool(_) => Value::Bool(value != 0.0),
+        _ => return Err(invalid("is not a numeric field")),
+    };
+    serde_json::from_value(fields).map_err(|e| invalid(&e.to_string()))
+}
+
+impl SweepRun {
+    /// 无窗口跑完这一组，扫描时多组并行，每个 App 只用单线程调度
+    pub fn run(&self, ticks: u64, metrics_interval: f32) -> RunSummary {
+        let mut app = headless_app(self.config.clone());
+        app.add_plugins(MetricsPlugin::default().interval(metrics_interval));
+        app.edit_schedule(Update, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+        app.edit_schedule(SimUpdate, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+        let mut app = run_app(app, ticks);
+        summarize(&mut app)
+    }
+}
+
+/// 用 jobs 个线程并行跑完所有运行，结果和 runs 顺序一致；每跑完一次调用 on_done(下标, 结果)
+pub fn run_parallel(
+    runs: &[SweepRun],
+    ticks: u64,
+    metrics_interval: f32,
+    jobs: usize,
+    mut on_done: impl FnMut(usize, &RunSummary),
+) -> Vec<RunSummary> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results = vec![None; runs.len()];
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(run) = runs.get(index) else {
+                    break;
+                };
+                let summary = run.run(ticks, metrics_interval);
+                if sender.send((index, summary)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, summary) in receiver.iter() {
+            on_done(index, &summary);
+            results[index] = Some(summary);
+        }
+    });
+
+    results.into_iter().flatten().collect()
+}
+
+/// 可用的 CPU 核数
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn summarize(app: &mut App) -> RunSummary {
+    let config = app.world.resource::<SimConfig>().clone();
+    let history = app.world.resource::<MetricsHistory>();
+    let samples: Vec<&MetricsSample> = history.samples.iter().collect();
+    let steady_len = (samples.len() as f64 * STEADY_STATE_FRACTION).ceil() as usize;
+    let steady = &samples[samples.len() - steady_len..];
+
+    let duration: f64 = steady.iter().map(|s| s.interval as f64).sum();
+    let deliveries: u32 = steady.iter().map(|s| s.deliveries).sum();
+    let throughput = if duration > 0.0 {
+        deliveries as f64 / duration * 60.0
+    } else {
+        0.0
+    };
+    let trip_time: f64 = steady
+        .iter()
+        .filter_map(|s| Some(s.avg_round_trip? as f64 * s.deliveries as f64))
+        .sum();
+    let round_trip = (deliveries > 0).then(|| trip_time / deliveries as f64);
+    let speed = config.ant_speed as f64;
+    let first_delivery = history.first_delivery;
+
+    let ants = app
+        .world
+        .query_filtered::<(), With<Ant>>()
+        .iter(&app.world)
+        .count();
+    let delivered = app
+        .world
+        .query::<&Colony>()
+        .iter(&app.world)
+        .map(|colony| colony.delivered)
+        .sum();
+
+    RunSummary {
+        first_delivery,
+        throughput,
+        round_trip,
+        trail_length: round_trip.map(|t| t / 2.0 * speed),
+        delivered,
+        ants,
+    }
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Config(ConfigError),
+    Invalid(String),
+    Serialize(ron::Error),
+    Write(io::Error),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Io(e) => write!(f, "failed to read sweep: {e}"),
+            SweepError::Parse(e) => write!(f, "failed to parse sweep: {e}"),
+            SweepError::Config(e) => write!(f, "{e}"),
+            SweepError::Invalid(reason) => write!(f, "invalid sweep: {reason}"),
+            SweepError::Serialize(e) => write!(f, "failed to serialize config: {e}"),
+            SweepError::Write(e) => write!(f, "failed to write config: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
@@ -0,0 +1,426 @@
+use std::{fs, path::Path};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use ron::ser::PrettyConfig;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    sweep::{run_parallel, set_param, RunSummary, SweepError, SweepRun},
+    SimConfig,
+};
+
+/// 遗传算法调参定义，从 RON 文件读取，示例见 `sweeps/tune_behaviour.ron`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TuneSpec {
+    /// 基础配置文件，不写时用默认参数；没有调的参数都取自这里
+    #[serde(default)]
+    pub config: Option<String>,
+    pub ticks: u64,
+    /// 每个个体用几个种子评估后取平均，所有个体用同一组种子
+    #[serde(default = "default_seeds")]
+    pub seeds: u64,
+    #[serde(default)]
+    pub first_seed: u64,
+    #[serde(default = "default_population")]
+    pub population: usize,
+    #[serde(default = "default_generations")]
+    pub generations: usize,
+    /// 每代原样保留的最好个体数
+    #[serde(default = "default_elite")]
+    pub elite: usize,
+    /// 锦标赛选择每次抽几个个体比较
+    #[serde(default = "default_tournament")]
+    pub tournament: usize,
+    /// 每个参数发生变异的概率
+    #[serde(default = "default_mutation_rate")]
+    pub mutation_rate: f64,
+    /// 变异幅度（正态分布的标准差），占参数取值范围的比例
+    #[serde(default = "default_mutation_scale")]
+    pub mutation_scale: f64,
+    /// 遗传算法本身的随机数种子，不设置时每次随机
+    #[serde(default)]
+    pub rng_seed: Option<u64>,
+    #[serde(default)]
+    pub fitness: Fitness,
+    pub params: Vec<TuneParam>,
+}
+
+fn default_seeds() -> u64 {
+    2
+}
+
+fn default_population() -> usize {
+    16
+}
+
+fn default_generations() -> usize {
+    10
+}
+
+fn default_elite() -> usize {
+    2
+}
+
+fn default_tournament() -> usize {
+    3
+}
+
+fn default_mutation_rate() -> f64 {
+    0.3
+}
+
+fn default_mutation_scale() -> f64 {
+    0.1
+}
+
+/// 一个要调的参数，name 是 [`SimConfig`] 的字段名（不区分大小写），取值限制在 [min, max]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TuneParam {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    /// 取值四舍五入；整数字段（比如蚂蚁数量）不写也会自动打开
+    #[serde(default)]
+    pub integer: bool,
+}
+
+/// 适应度，越大越好
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum Fitness {
+    /// 整次运行平均每步送达的食物
+    #[default]
+    DeliveredPerTick,
+    /// 稳定阶段每分钟送达的食物
+    Throughput,
+}
+
+impl Fitness {
+    pub fn score(&self, summary: &RunSummary, ticks: u64) -> f64 {
+        match self {
+            Fitness::DeliveredPerTick => summary.delivered as f64 / ticks.max(1) as f64,
+            Fitness::Throughput => summary.throughput,
+        }
+    }
+}
+
+/// 一组参数取值，顺序和 [`TuneSpec::params`] 一样
+#[derive(Debug, Clone)]
+pub struct Individual {
+    pub genes: Vec<f64>,
+    /// 还没评估时为 None
+    pub fitness: Option<f64>,
+}
+
+/// 一代的统计
+#[derive(Debug, Clone, Copy)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub best: f64,
+    pub mean: f64,
+}
+
+impl TuneSpec {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SweepError> {
+        let text = fs::read_to_string(path).map_err(SweepError::Io)?;
+        ron::from_str(&text).map_err(SweepError::Parse)
+    }
+}
+
+/// 遗传算法：锦标赛选择、按参数混合的交叉、高斯变异，最好的几个个体直接进入下一代；
+/// 模拟是确定的，保留下来的个体不用重新评估
+pub struct Tuner {
+    pub spec: TuneSpec,
+    base: SimConfig,
+    rng: ChaCha8Rng,
+    pub generation: usize,
+    pub population: Vec<Individual>,
+}
+
+impl Tuner {
+    /// 第一个个体是基础配置里的当前值，其余在范围内随机取
+    pub fn new(mut spec: TuneSpec) -> Result<Self, SweepError> {
+        let base = match &spec.config {
+            Some(path) => SimConfig::load(path).map_err(SweepError::Config)?,
+            None => SimConfig::default(),
+        };
+        if spec.params.is_empty() {
+            return Err(SweepError::Invalid("`params` is empty".into()));
+        }
+        if spec.seeds == 0 {
+            return Err(SweepError::Invalid("`seeds` must be at least 1".into()));
+        }
+        if spec.population < 2 {
+            return Err(SweepError::Invalid(
+                "`population` must be at least 2".into(),
+            ));
+        }
+        let fields =
+            serde_json::to_value(&base).map_err(|e| SweepError::Invalid(format!("config: {e}")))?;
+        for param in spec.params.iter_mut() {
+            param.name = param.name.to_lowercase();
+            // 整数字段取到小数时 set_param 会失败，评估到一半才报错
+            if let Some(Value::Number(n)) = fields.get(&param.name) {
+                param.integer |= !n.is_f64();
+            }
+            if param.min.is_nan() || param.max.is_nan() || param.min > param.max {
+                return Err(SweepError::Invalid(format!(
+                    "`{}` needs min <= max",
+                    param.name
+                )));
+            }
+            // 两端都要是合法的配置，中间的值才能放心取
+            for value in [param.min, param.max] {
+                let value = if param.integer { value.round() } else { value };
+                set_param(&base, &param.name, value)?
+                    .validate()
+                    .map_err(SweepError::Config)?;
+            }
+        }
+
+        let mut rng = match spec.rng_seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        let current = current_values(&base, &spec.params)?;
+        let mut population = vec![Individual {
+            genes: clamp_genes(&spec.params, current),
+            fitness: None,
+        }];
+        while population.len() < spec.population {
+            let genes = spec
+                .params
+                .iter()
+                .map(|param| rng.gen_range(param.min..=param.max))
+                .collect();
+            population.push(Individual {
+                genes: clamp_genes(&spec.params, genes),
+                fitness: None,
+            });
+        }
+
+        Ok(Self {
+            spec,
+            base,
+            rng,
+            generation: 0,
+            population,
+        })
+    }
+
+    /// 把一组参数写进基础配置，种子清空
+    pub fn config(&self, genes: &[f64]) -> Result<SimConfig, SweepError> {
+        let mut config = self.base.clone();
+        for (param, value) in self.spec.params.iter().zip(genes) {
+            config = set_param(&config, &param.name, *value)?;
+        }
+        config.validate().map_err(SweepError::Config)?;
+        config.seed = None;
+        Ok(config)
+    }
+
+    /// 并行评估这一代还没有适应度的个体，每跑完一次调用 on_done(个体下标, 种子, 结果)
+    pub fn evaluate(
+        &mut self,
+        jobs: usize,
+        mut on_done: impl FnMut(usize, u64, &RunSummary),
+    ) -> Result<GenerationStats, SweepError> {
+        let pending: Vec<usize> = (0..self.population.len())
+            .filter(|&i| self.population[i].fitness.is_none())
+            .collect();
+
+        let mut runs = Vec::new();
+        for &index in pending.iter() {
+            let config = self.config(&self.population[index].genes)?;
+            let params: Vec<(String, f64)> = self
+                .spec
+                .params
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(self.population[index].genes.iter().copied())
+                .collect();
+            for seed in self.spec.first_seed..self.spec.first_seed + self.spec.seeds {
+                let mut config = config.clone();
+                config.seed = Some(seed);
+                runs.push(SweepRun {
+                    params: params.clone(),
+                    seed,
+                    config,
+                });
+            }
+        }
+
+        let seeds = self.spec.seeds as usize;
+        let results = run_parallel(&runs, self.spec.ticks, 1.0, jobs, |i, summary| {
+            on_done(pending[i / seeds], runs[i].seed, summary)
+        });
+        for (&index, summaries) in pending.iter().zip(results.chunks(seeds)) {
+            let total: f64 = summaries
+                .iter()
+                .map(|summary| self.spec.fitness.score(summary, self.spec.ticks))
+                .sum();
+            self.population[index].fitness = Some(total / summaries.len() as f64);
+        }
+
+        let scores: Vec<f64> = self
+            .population
+            .iter()
+            .filter_map(|individual| individual.fitness)
+            .collect();
+        Ok(GenerationStats {
+            generation: self.generation,
+            best: scores.iter().copied().fold(f64::MIN, f64::max),
+            mean: scores.iter().sum::<f64>() / scores.len() as f64,
+        })
+    }
+
+    /// 已评估的个体里最好的
+    pub fn best(&self) -> Option<&Individual> {
+        self.population
+            .iter()
+            .filter(|individual| individual.fitness.is_some())
+            .max_by(|a, b| a.fitness.unwrap().total_cmp(&b.fitness.unwrap()))
+    }
+
+    /// 用当前（已评估的）一代生成下一代
+    pub fn next_generation(&mut self) {
+        self.population.sort_by(|a, b| {
+            let score = |i: &Individual| i.fitness.unwrap_or(f64::MIN);
+            score(b).total_cmp(&score(a))
+        });
+
+        let size = self.population.len();
+        let mut next: Vec<Individual> = self
+            .population
+            .iter()
+            .take(self.spec.elite.min(size))
+            .cloned()
+            .collect();
+        while next.len() < size {
+            let a = self.select();
+            let b = self.select();
+            let child = self.crossover(a, b);
+            let genes = self.mutate(child);
+            next.push(Individual {
+                genes,
+                fitness: None,
+            });
+        }
+
+        self.population = next;
+        self.generation += 1;
+    }
+
+    /// 锦标赛选择，返回下标
+    fn select(&mut self) -> usize {
+        let size = self.population.len();
+        (0..self.spec.tournament.max(1))
+            .map(|_| self.rng.gen_range(0..size))
+            .max_by(|&a, &b| {
+                let score = |i: usize| self.population[i].fitness.unwrap_or(f64::MIN);
+                score(a).total_cmp(&score(b))
+            })
+            .unwrap_or(0)
+    }
+
+    /// 每个参数在两个父代之间随机取一个混合值
+    fn crossover(&mut self, a: usize, b: usize) -> Vec<f64> {
+        let (a, b) = (&self.population[a].genes, &self.population[b].genes);
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| {
+                let t: f64 = self.rng.gen();
+                x + (y - x) * t
+            })
+            .collect()
+    }
+
+    fn mutate(&mut self, mut genes: Vec<f64>) -> Vec<f64> {
+        for (gene, param) in genes.iter_mut().zip(self.spec.params.iter()) {
+            if self.rng.gen_bool(self.spec.mutation_rate.clamp(0.0, 1.0)) {
+                *gene +=
+                    gaussian(&mut self.rng) * self.spec.mutation_scale * (param.max - param.min);
+            }
+        }
+        clamp_genes(&self.spec.params, genes)
+    }
+}
+
+fn clamp_genes(params: &[TuneParam], genes: Vec<f64>) -> Vec<f64> {
+    genes
+        .into_iter()
+        .zip(params.iter())
+        .map(|(gene, param)| {
+            let gene = gene.clamp(param.min, param.max);
+            if param.integer {
+                gene.round()
+            } else {
+                gene
+            }
+        })
+        .collect()
+}
+
+/// 基础配置里各参数的当前值
+fn current_values(config: &SimConfig, params: &[TuneParam]) -> Result<Vec<f64>, SweepError> {
+    let fields =
+        serde_json::to_value(config).map_err(|e| SweepError::Invalid(format!("config: {e}")))?;
+    params
+        .iter()
+        .map(|param| {
+            fields
+                .get(&param.name)
+                .and_then(|value| value.as_f64())
+                .ok_or_else(|| {
+                    SweepError::Invalid(format!("`{}` is not a numeric field", param.name))
+                })
+        })
+        .collect()
+}
+
+/// 标准正态分布（Box-Muller）
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+}
+
+/// 把配置写成可以直接用 `--config` 加载的 RON 文件，comment 写在文件开头
+pub fn save_config(
+    config: &SimConfig,
+    path: impl AsRef<Path>,
+    comment: &str,
+) -> Result<(), SweepError> {
+    let text = ron::ser::to_string_pretty(config, PrettyConfig::default())
+        .map_err(SweepError::Serialize)?;
+    let header: String = comment.lines().map(|line| format!("// {line}\n")).collect();
+    fs::write(path, header + &text + "\n").map_err(SweepError::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 整数字段没写 integer 时也按整数取值，每个个体都能生成配置
+    #[test]
+    fn integer_fields_are_rounded() {
+        let spec: TuneSpec = ron::from_str(
+            r#"(
+                ticks: 10,
+                seeds: 1,
+                population: 8,
+                generations: 1,
+                rng_seed: Some(3),
+                params: [(name: "num_ants", min: 10.0, max: 20.0)],
+            )"#,
+        )
+        .unwrap();
+        let tuner = Tuner::new(spec).unwrap();
+        assert!(tuner.spec.params[0].integer);
+        for individual in tuner.population.iter() {
+            assert_eq!(individual.genes[0].fract(), 0.0);
+            tuner.config(&individual.genes).unwrap();
+        }
+    }
+}
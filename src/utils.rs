@@ -2,19 +2,38 @@ use std::f32::consts::PI;
 
 use bevy::{
     math::{vec2, vec3},
-    prelude::{Vec2, Vec3},
+    prelude::{Resource, Vec2, Vec3},
 };
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{H, PH_UNIT_GRID_SIZE, W};
+use crate::SimConfig;
 
-pub fn get_rand_unit_vec2() -> Vec2 {
-    let rand_vec3 = get_rand_unit_vec3();
+/// 模拟里所有随机数都从这里取，相同的种子和配置得到相同的结果
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct SimRng {
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
+}
+
+impl SimRng {
+    /// 没有指定种子时随机取一个，通过 `seed` 字段可以复现
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| thread_rng().gen());
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+pub fn get_rand_unit_vec2(rng: &mut impl Rng) -> Vec2 {
+    let rand_vec3 = get_rand_unit_vec3(rng);
     vec2(rand_vec3.x, rand_vec3.y)
 }
 
-pub fn get_rand_unit_vec3() -> Vec3 {
-    let mut rng = thread_rng();
+pub fn get_rand_unit_vec3(rng: &mut impl Rng) -> Vec3 {
     vec3(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0).normalize()
 }
 
@@ -33,9 +52,13 @@ pub fn calc_rotatio_angle(v1: &Vec3, v2: &Vec3) -> f32 {
 }
 
 /// Convert from center to top left co-ords
-pub fn window_to_grid(x: i32, y: i32) -> (i32, i32) {
-    let (tx, ty) = (x + (W as usize / 2) as i32, (H as usize / 2) as i32 - y);
-    let (tx, ty) = (tx / PH_UNIT_GRID_SIZE as i32, ty / PH_UNIT_GRID_SIZE as i32);
+pub fn window_to_grid(x: i32, y: i32, config: &SimConfig) -> (i32, i32) {
+    let (tx, ty) = (
+        x + (config.w as usize / 2) as i32,
+        (config.h as usize / 2) as i32 - y,
+    );
+    let unit = config.ph_unit_grid_size as i32;
+    let (tx, ty) = (tx.div_euclid(unit), ty.div_euclid(unit));
 
     (tx, ty)
 }